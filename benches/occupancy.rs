@@ -0,0 +1,192 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use ssm::chunk::ChunkMap;
+use ssm::lattice::Bounds;
+use ssm::loader;
+use ssm::orientation::Orientation;
+use ssm::world::{Footprint, PartInstanceId, World, WorldCell};
+use std::collections::HashMap;
+
+/// A solid `n`³ block of cells, like a densely built site.
+fn block(n: i32) -> Vec<WorldCell> {
+    let mut cells = Vec::with_capacity((n * n * n) as usize);
+    for z in 0..n {
+        for y in 0..n {
+            for x in 0..n {
+                cells.push(WorldCell { x, y, z });
+            }
+        }
+    }
+    cells
+}
+
+/// Scattered probe cells, about half of them inside the block.
+fn probes(n: i32, count: usize) -> Vec<WorldCell> {
+    let mut state = 0x9e37_79b9_u32;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        (state % (2 * n as u32)) as i32
+    };
+    (0..count)
+        .map(|_| WorldCell { x: next(), y: next(), z: next() })
+        .collect()
+}
+
+fn insert(c: &mut Criterion) {
+    let mut group = c.benchmark_group("insert");
+    for n in [32, 96] {
+        let cells = block(n);
+        group.bench_with_input(BenchmarkId::new("hash_map", n), &cells, |b, cells| {
+            b.iter(|| {
+                let mut map = HashMap::new();
+                for (i, cell) in cells.iter().enumerate() {
+                    map.insert(*cell, PartInstanceId(i as u64));
+                }
+                black_box(map.len())
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("chunk_map", n), &cells, |b, cells| {
+            b.iter(|| {
+                let mut map = ChunkMap::new();
+                for (i, cell) in cells.iter().enumerate() {
+                    map.insert(*cell, PartInstanceId(i as u64));
+                }
+                black_box(map.len())
+            })
+        });
+    }
+    group.finish();
+}
+
+fn lookup(c: &mut Criterion) {
+    let n = 96;
+    let cells = block(n);
+    let probes = probes(n, 100_000);
+
+    let hash: HashMap<_, _> = cells
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (*c, PartInstanceId(i as u64)))
+        .collect();
+    let mut chunked = ChunkMap::new();
+    for (i, cell) in cells.iter().enumerate() {
+        chunked.insert(*cell, PartInstanceId(i as u64));
+    }
+
+    let mut group = c.benchmark_group("lookup");
+    group.bench_function("hash_map", |b| {
+        b.iter(|| probes.iter().filter(|p| hash.contains_key(*p)).count())
+    });
+    group.bench_function("chunk_map", |b| {
+        b.iter(|| probes.iter().filter(|p| chunked.contains(**p)).count())
+    });
+
+    let region = Bounds { x: [10, 41], y: [10, 41], z: [10, 41] };
+    group.bench_function("region/hash_map", |b| {
+        b.iter(|| {
+            let mut found: Vec<_> = hash
+                .iter()
+                .filter(|(c, _)| {
+                    (10..=41).contains(&c.x) && (10..=41).contains(&c.y) && (10..=41).contains(&c.z)
+                })
+                .map(|(c, id)| (*c, *id))
+                .collect();
+            found.sort_by_key(|(c, _)| (c.z, c.y, c.x));
+            black_box(found.len())
+        })
+    });
+    group.bench_function("region/chunk_map", |b| {
+        b.iter(|| black_box(chunked.region(&region).len()))
+    });
+    group.finish();
+}
+
+/// The occupancy half of placing a part, as `World` does it: refuse if any
+/// cell is taken, otherwise claim them all.
+trait Occupancy {
+    fn taken(&self, cell: WorldCell) -> bool;
+    fn claim(&mut self, cell: WorldCell, id: PartInstanceId);
+}
+
+impl Occupancy for HashMap<WorldCell, PartInstanceId> {
+    fn taken(&self, cell: WorldCell) -> bool {
+        self.contains_key(&cell)
+    }
+
+    fn claim(&mut self, cell: WorldCell, id: PartInstanceId) {
+        self.insert(cell, id);
+    }
+}
+
+impl Occupancy for ChunkMap<PartInstanceId> {
+    fn taken(&self, cell: WorldCell) -> bool {
+        self.contains(cell)
+    }
+
+    fn claim(&mut self, cell: WorldCell, id: PartInstanceId) {
+        self.insert(cell, id);
+    }
+}
+
+/// Places every footprint that fits, returning how many did.
+fn place_all(occupancy: &mut impl Occupancy, footprints: &[Footprint]) -> usize {
+    let mut placed = 0;
+    for (i, footprint) in footprints.iter().enumerate() {
+        if footprint.cells.iter().any(|c| occupancy.taken(*c)) {
+            continue;
+        }
+        for cell in &footprint.cells {
+            occupancy.claim(*cell, PartInstanceId(i as u64));
+        }
+        placed += 1;
+    }
+    placed
+}
+
+fn place_bricks(c: &mut Criterion) {
+    let part = loader::load_part_dir("assets/parts/lego/3001").unwrap();
+    let bounds = part.lattice.bounds;
+    let (w, d, h) = (
+        bounds.x[1] - bounds.x[0] + 1,
+        bounds.y[1] - bounds.y[0] + 1,
+        bounds.z[1] - bounds.z[0] + 1,
+    );
+
+    // A 10x10x10 stack of bricks, then the same stack again one stud over,
+    // where every brick collides.
+    let mut offsets = Vec::new();
+    for shift in [0, 1] {
+        for z in 0..10 {
+            for y in 0..10 {
+                for x in 0..10 {
+                    offsets.push(WorldCell { x: x * w + shift, y: y * d, z: z * h });
+                }
+            }
+        }
+    }
+    let footprints: Vec<Footprint> = offsets
+        .iter()
+        .map(|at| Footprint::new(&part, *at, Orientation::Deg0))
+        .collect();
+
+    let mut group = c.benchmark_group("place");
+    group.bench_function("hash_map", |b| {
+        b.iter(|| black_box(place_all(&mut HashMap::new(), &footprints)))
+    });
+    group.bench_function("chunk_map", |b| {
+        b.iter(|| black_box(place_all(&mut ChunkMap::new(), &footprints)))
+    });
+    // The whole of `World::place_part`, connections and protrusions too.
+    group.bench_function("world", |b| {
+        b.iter(|| {
+            let mut world = World::new();
+            let placed = offsets.iter().filter(|at| world.place_part(&part, **at).is_ok());
+            black_box(placed.count())
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, insert, lookup, place_bricks);
+criterion_main!(benches);
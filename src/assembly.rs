@@ -0,0 +1,190 @@
+use crate::support::rests_on_ground;
+use crate::world::{PartInstanceId, World, WorldCell};
+use std::collections::BTreeSet;
+
+/// A build sequence for a world.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AssemblyPlan {
+    /// Instances in the order to place them.
+    pub order: Vec<PartInstanceId>,
+    /// Instances no order can reach: unsupported, or boxed in by the parts
+    /// they mate with. Empty when the whole model can be built.
+    pub stuck: Vec<PartInstanceId>,
+}
+
+impl AssemblyPlan {
+    pub fn is_feasible(&self) -> bool {
+        self.stuck.is_empty()
+    }
+}
+
+/// Greedy bottom-up planner.
+///
+/// A part can go in once it rests on the ground or mates with a placed part,
+/// and it can be pushed home: the connectors mating with placed parts all
+/// face the same way, which is the direction the part moves in (tubes face
+/// down, so a brick drops onto studs from above), and no placed part sits in
+/// the way along that approach. Among the parts that can go in, the lowest
+/// one is placed first, ties broken by id.
+pub fn plan_assembly(world: &World) -> AssemblyPlan {
+    let mut plan = AssemblyPlan::default();
+    let mut placed = BTreeSet::new();
+    let mut extent: Option<(WorldCell, WorldCell)> = None;
+
+    let mut pending: Vec<_> = world
+        .instances()
+        .map(|(id, _)| (world.bottom_layer(id).unwrap_or(i32::MAX), id))
+        .collect();
+    pending.sort();
+
+    while let Some(pos) = pending
+        .iter()
+        .position(|&(_, id)| can_place(world, id, &placed, extent))
+    {
+        let (_, id) = pending.remove(pos);
+        for &c in world.instance_cells(id).unwrap_or(&[]) {
+            extent = Some(match extent {
+                None => (c, c),
+                Some((lo, hi)) => (
+                    WorldCell { x: lo.x.min(c.x), y: lo.y.min(c.y), z: lo.z.min(c.z) },
+                    WorldCell { x: hi.x.max(c.x), y: hi.y.max(c.y), z: hi.z.max(c.z) },
+                ),
+            });
+        }
+        placed.insert(id);
+        plan.order.push(id);
+    }
+
+    plan.stuck = pending.into_iter().map(|(_, id)| id).collect();
+    plan.stuck.sort();
+    plan
+}
+
+fn can_place(
+    world: &World,
+    id: PartInstanceId,
+    placed: &BTreeSet<PartInstanceId>,
+    extent: Option<(WorldCell, WorldCell)>,
+) -> bool {
+    let graph = world.connections();
+
+    let mut motion: Option<[i32; 3]> = None;
+    for other in graph.neighbors(id) {
+        if !placed.contains(&other) {
+            continue;
+        }
+        for mate in graph.mates(id, other) {
+            let Some(c) = world.connector(id, mate.local) else {
+                continue;
+            };
+            let dir = dominant_axis(c.normal.to_array());
+            match motion {
+                None => motion = Some(dir),
+                Some(m) if m != dir => return false,
+                Some(_) => {}
+            }
+        }
+    }
+
+    // Nothing to push against: it has to stand on the ground, and is set
+    // down from above.
+    let motion = match motion {
+        Some(m) => m,
+        None if rests_on_ground(world, id) => [0, 0, -1],
+        None => return false,
+    };
+
+    let Some((lo, hi)) = extent else {
+        return true;
+    };
+
+    for &cell in world.instance_cells(id).unwrap_or(&[]) {
+        let mut c = cell;
+        loop {
+            c = WorldCell { x: c.x - motion[0], y: c.y - motion[1], z: c.z - motion[2] };
+            let outside = c.x < lo.x
+                || c.x > hi.x
+                || c.y < lo.y
+                || c.y > hi.y
+                || c.z < lo.z
+                || c.z > hi.z;
+            if outside {
+                break;
+            }
+            if let Some(other) = world.instance_at(c) {
+                if other != id && placed.contains(&other) {
+                    return false;
+                }
+            }
+        }
+    }
+
+    true
+}
+
+/// The axis direction closest to `v`, as a unit step.
+fn dominant_axis(v: [f32; 3]) -> [i32; 3] {
+    let axis = (0..3)
+        .max_by(|&a, &b| v[a].abs().total_cmp(&v[b].abs()))
+        .expect("three axes");
+    let mut step = [0; 3];
+    step[axis] = if v[axis] < 0.0 { -1 } else { 1 };
+    step
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader;
+
+    fn at(x: i32, y: i32, z: i32) -> WorldCell {
+        WorldCell { x, y, z }
+    }
+
+    #[test]
+    fn builds_bottom_up() {
+        let brick = loader::load_part_dir("assets/parts/lego/3001").unwrap();
+        let mut world = World::new();
+
+        // Placed top first, so ids run against the build order.
+        let top = world.place_part(&brick, at(0, 2, 6)).unwrap();
+        let left = world.place_part(&brick, at(0, 0, 3)).unwrap();
+        let right = world.place_part(&brick, at(0, 4, 3)).unwrap();
+        let base = world.place_part(&brick, at(0, 2, 0)).unwrap();
+
+        let plan = plan_assembly(&world);
+        assert!(plan.is_feasible());
+        assert_eq!(plan.order, vec![base, left, right, top]);
+    }
+
+    #[test]
+    fn hanging_parts_follow_what_holds_them() {
+        let brick = loader::load_part_dir("assets/parts/lego/3001").unwrap();
+        let slim = loader::load_part_dir("assets/parts/lego/3004").unwrap();
+        let mut world = World::new();
+
+        let low = world.place_part(&brick, at(0, 0, 0)).unwrap();
+        let high = world.place_part(&brick, at(0, 0, 3)).unwrap();
+        let roof = world.place_part(&brick, at(0, 3, 6)).unwrap();
+        // Under the overhang, held only by the roof's tubes.
+        let hanging = world.place_part(&slim, at(0, 5, 3)).unwrap();
+
+        let plan = plan_assembly(&world);
+        assert_eq!(plan.order, vec![low, high, roof, hanging]);
+    }
+
+    #[test]
+    fn reports_parts_with_no_way_in() {
+        let brick = loader::load_part_dir("assets/parts/lego/3001").unwrap();
+        let block = loader::load_part_dir("assets/parts/lego/3003").unwrap();
+        let mut world = World::new();
+
+        let base = world.place_part(&brick, at(0, 0, 0)).unwrap();
+        let floating = world.place_part(&block, at(10, 0, 9)).unwrap();
+
+        let plan = plan_assembly(&world);
+        assert_eq!(plan.order, vec![base]);
+        assert_eq!(plan.stuck, vec![floating]);
+        assert!(!plan.is_feasible());
+    }
+}
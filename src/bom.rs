@@ -0,0 +1,265 @@
+use crate::catalog::Catalog;
+use crate::world::World;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// One purchasable line: a part in one color.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BomLine {
+    pub part: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    pub quantity: usize,
+}
+
+/// Parts needed for a world, ordered by part id, then color.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct BillOfMaterials {
+    pub lines: Vec<BomLine>,
+}
+
+impl BillOfMaterials {
+    /// Counts instances by part id and color; placed submodels count as the
+    /// parts they resolve to. Colors with the same [`color_key`] are one
+    /// color, named as first seen.
+    pub fn from_world(world: &World, catalog: &Catalog) -> Result<Self, String> {
+        let mut counts: BTreeMap<LineKey, (Option<&str>, usize)> = BTreeMap::new();
+        for (_, inst) in world.instances() {
+            let color = inst.color.as_deref().map(str::trim);
+            let key = (inst.part.as_str(), color.map(color_key));
+            counts.entry(key).or_insert((color, 0)).1 += 1;
+        }
+
+        catalog.require_all(counts.keys().map(|(part, _)| *part))?;
+
+        let lines = counts
+            .into_iter()
+            .map(|((part, _), (color, quantity))| {
+                let meta = &catalog.get(part).expect("checked above").meta;
+                BomLine {
+                    part: part.to_string(),
+                    name: meta.name.clone(),
+                    category: meta.category.clone(),
+                    color: color.map(str::to_string),
+                    quantity,
+                }
+            })
+            .collect();
+
+        Ok(Self { lines })
+    }
+
+    pub fn total(&self) -> usize {
+        self.lines.iter().map(|l| l.quantity).sum()
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut out = String::from("part,name,category,color,quantity\n");
+        for l in &self.lines {
+            out.push_str(&format!(
+                "{},{},{},{},{}\n",
+                csv_field(&l.part),
+                csv_field(&l.name),
+                csv_field(l.category.as_deref().unwrap_or("")),
+                csv_field(l.color.as_deref().unwrap_or("")),
+                l.quantity
+            ));
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("failed to serialize BOM: {}", e))
+    }
+
+    /// BrickLink wanted-list XML. Lines without a color accept any color;
+    /// colors BrickLink has no id for, and parts outside the `lego` system,
+    /// are errors rather than silently wrong orders.
+    pub fn to_bricklink_xml(&self) -> Result<String, String> {
+        let mut out = String::from("<INVENTORY>\n");
+        for l in &self.lines {
+            let item = l
+                .part
+                .strip_prefix("lego:")
+                .ok_or_else(|| format!("not a LEGO part: {}", l.part))?;
+
+            out.push_str("  <ITEM>\n");
+            out.push_str("    <ITEMTYPE>P</ITEMTYPE>\n");
+            out.push_str(&format!("    <ITEMID>{}</ITEMID>\n", xml_escape(item)));
+            if let Some(color) = &l.color {
+                let id = bricklink_color(color)
+                    .ok_or_else(|| format!("no BrickLink color for {}: {}", l.part, color))?;
+                out.push_str(&format!("    <COLOR>{}</COLOR>\n", id));
+            }
+            out.push_str(&format!("    <MINQTY>{}</MINQTY>\n", l.quantity));
+            out.push_str("  </ITEM>\n");
+        }
+        out.push_str("</INVENTORY>\n");
+        Ok(out)
+    }
+}
+
+/// Part id and [`color_key`] of a line.
+type LineKey<'a> = (&'a str, Option<String>);
+
+/// What two spellings of one color name share: lowercase, without spaces,
+/// dashes or underscores, and with "grey" spelled "gray". "Light Bluish
+/// Gray" and "light-bluish-grey" both become `lightbluishgray`.
+pub fn color_key(name: &str) -> String {
+    name.chars()
+        .filter(|c| !c.is_whitespace() && *c != '-' && *c != '_')
+        .collect::<String>()
+        .to_lowercase()
+        .replace("grey", "gray")
+}
+
+/// BrickLink color id for a color name, compared by [`color_key`].
+pub fn bricklink_color(name: &str) -> Option<u32> {
+    let id = match color_key(name).as_str() {
+        "white" => 1,
+        "tan" => 2,
+        "yellow" => 3,
+        "orange" => 4,
+        "red" => 5,
+        "green" => 6,
+        "blue" => 7,
+        "brown" => 8,
+        "lightgray" => 9,
+        "darkgray" => 10,
+        "black" => 11,
+        "darkbluishgray" => 85,
+        "lightbluishgray" => 86,
+        _ => return None,
+    };
+    Some(id)
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::WorldCell;
+
+    fn sample() -> (World, Catalog) {
+        let catalog = Catalog::load_dir("assets/parts").unwrap();
+        let brick = catalog.get("lego:3001").unwrap();
+        let block = catalog.get("lego:3003").unwrap();
+        let mut world = World::new();
+
+        for (x, color) in [(0, Some("red")), (4, Some("red")), (8, None)] {
+            let id = world.place_part(brick, WorldCell { x, y: 0, z: 0 }).unwrap();
+            world.set_color(id, color.map(str::to_string)).unwrap();
+        }
+        let id = world.place_part(block, WorldCell { x: 0, y: 0, z: 3 }).unwrap();
+        world.set_color(id, Some("Light Bluish Gray".to_string())).unwrap();
+
+        (world, catalog)
+    }
+
+    #[test]
+    fn counts_by_part_and_color() {
+        let (world, catalog) = sample();
+        let bom = BillOfMaterials::from_world(&world, &catalog).unwrap();
+
+        assert_eq!(bom.total(), 4);
+        assert_eq!(
+            bom.lines[1],
+            BomLine {
+                part: "lego:3001".to_string(),
+                name: "Brick 2x4".to_string(),
+                category: Some("Brick".to_string()),
+                color: Some("red".to_string()),
+                quantity: 2,
+            }
+        );
+        assert_eq!(bom.lines[0].color, None);
+
+        assert_eq!(
+            bom.to_csv(),
+            "part,name,category,color,quantity\n\
+             lego:3001,Brick 2x4,Brick,,1\n\
+             lego:3001,Brick 2x4,Brick,red,2\n\
+             lego:3003,Brick 2x2,Brick,Light Bluish Gray,1\n"
+        );
+
+        let json: serde_json::Value = serde_json::from_str(&bom.to_json().unwrap()).unwrap();
+        assert_eq!(json["lines"][1]["quantity"], 2);
+        assert!(json["lines"][0].get("color").is_none());
+    }
+
+    #[test]
+    fn exports_a_bricklink_wanted_list() {
+        let (mut world, catalog) = sample();
+        let xml = BillOfMaterials::from_world(&world, &catalog)
+            .unwrap()
+            .to_bricklink_xml()
+            .unwrap();
+
+        assert!(xml.starts_with("<INVENTORY>\n"));
+        assert_eq!(xml.matches("<ITEM>").count(), 3);
+        assert!(xml.contains(
+            "    <ITEMID>3001</ITEMID>\n    <COLOR>5</COLOR>\n    <MINQTY>2</MINQTY>\n"
+        ));
+        assert!(xml.contains("    <ITEMID>3003</ITEMID>\n    <COLOR>86</COLOR>\n"));
+
+        let id = world
+            .place_part(catalog.get("lego:3004").unwrap(), WorldCell { x: 20, y: 0, z: 0 })
+            .unwrap();
+        world.set_color(id, Some("#123456".to_string())).unwrap();
+        let err = BillOfMaterials::from_world(&world, &catalog)
+            .unwrap()
+            .to_bricklink_xml()
+            .unwrap_err();
+        assert_eq!(err, "no BrickLink color for lego:3004: #123456");
+    }
+
+    #[test]
+    fn merges_spellings_of_one_color() {
+        let (mut world, catalog) = sample();
+        let id = world
+            .place_part(catalog.get("lego:3001").unwrap(), WorldCell { x: 20, y: 0, z: 0 })
+            .unwrap();
+        world.set_color(id, Some(" RED ".to_string())).unwrap();
+        let id = world
+            .place_part(catalog.get("lego:3003").unwrap(), WorldCell { x: 20, y: 0, z: 3 })
+            .unwrap();
+        world.set_color(id, Some("light bluish gray".to_string())).unwrap();
+        let id = world
+            .place_part(catalog.get("lego:3003").unwrap(), WorldCell { x: 24, y: 0, z: 0 })
+            .unwrap();
+        world.set_color(id, Some("light-bluish-grey".to_string())).unwrap();
+
+        let bom = BillOfMaterials::from_world(&world, &catalog).unwrap();
+        assert_eq!(bom.lines.len(), 3);
+        assert_eq!(bom.lines[1].color.as_deref(), Some("red"));
+        assert_eq!(bom.lines[1].quantity, 3);
+        assert_eq!(bom.lines[2].color.as_deref(), Some("Light Bluish Gray"));
+        assert_eq!(bom.lines[2].quantity, 3);
+
+        let xml = bom.to_bricklink_xml().unwrap();
+        assert_eq!(xml.matches("<COLOR>5</COLOR>").count(), 1);
+        assert_eq!(xml.matches("<COLOR>86</COLOR>").count(), 1);
+    }
+
+    #[test]
+    fn csv_quotes_awkward_fields() {
+        assert_eq!(csv_field("Brick, \"2x4\""), "\"Brick, \"\"2x4\"\"\"");
+        assert_eq!(csv_field("plain"), "plain");
+    }
+}
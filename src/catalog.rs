@@ -0,0 +1,114 @@
+use crate::loader::{load_part_dir, Part};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+/// Parts keyed by their catalog id (`lego:3001`).
+#[derive(Default)]
+pub struct Catalog {
+    parts: BTreeMap<String, Part>,
+}
+
+impl Catalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads every `<system>/<part_id>` directory under `root`, e.g.
+    /// `assets/parts`.
+    pub fn load_dir<P: AsRef<Path>>(root: P) -> Result<Self, String> {
+        let root = root.as_ref();
+        let mut catalog = Self::new();
+
+        for system in sorted_dirs(root)? {
+            for part_dir in sorted_dirs(&system)? {
+                if !part_dir.join("part.toml").is_file() {
+                    continue;
+                }
+                let part = load_part_dir(&part_dir)
+                    .map_err(|e| format!("{}: {}", part_dir.display(), e))?;
+                catalog.insert(part)?;
+            }
+        }
+
+        Ok(catalog)
+    }
+
+    pub fn insert(&mut self, part: Part) -> Result<(), String> {
+        if self.parts.contains_key(&part.meta.id) {
+            return Err(format!("duplicate part id: {}", part.meta.id));
+        }
+        self.parts.insert(part.meta.id.clone(), part);
+        Ok(())
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Part> {
+        self.parts.get(id)
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.parts.contains_key(id)
+    }
+
+    /// Checks that every id is in the catalog, naming all missing ones at
+    /// once rather than stopping at the first.
+    pub fn require_all<'a>(&self, ids: impl IntoIterator<Item = &'a str>) -> Result<(), String> {
+        let missing: BTreeSet<&str> = ids.into_iter().filter(|id| !self.contains(id)).collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        Err(format!(
+            "missing parts: {}",
+            missing.into_iter().collect::<Vec<_>>().join(", ")
+        ))
+    }
+
+    pub fn len(&self) -> usize {
+        self.parts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.parts.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Part> + '_ {
+        self.parts.values()
+    }
+}
+
+fn sorted_dirs(dir: &Path) -> Result<Vec<std::path::PathBuf>, String> {
+    let entries = fs::read_dir(dir)
+        .map_err(|e| format!("failed to read {}: {}", dir.display(), e))?;
+
+    let mut dirs = Vec::new();
+    for entry in entries {
+        let path = entry
+            .map_err(|e| format!("failed to read {}: {}", dir.display(), e))?
+            .path();
+        if path.is_dir() {
+            dirs.push(path);
+        }
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_asset_tree() {
+        let catalog = Catalog::load_dir("assets/parts").unwrap();
+
+        assert_eq!(catalog.len(), 4);
+        assert_eq!(catalog.get("lego:3003").unwrap().meta.name, "Brick 2x2");
+        assert!(!catalog.contains("lego:9999"));
+
+        assert!(catalog.require_all(["lego:3001", "lego:3004"]).is_ok());
+        assert_eq!(
+            catalog.require_all(["lego:9999", "lego:3001", "lego:1234", "lego:9999"]),
+            Err("missing parts: lego:1234, lego:9999".to_string())
+        );
+    }
+}
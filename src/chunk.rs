@@ -0,0 +1,254 @@
+use crate::lattice::Bounds;
+use crate::world::WorldCell;
+use std::collections::HashMap;
+
+/// Edge length of a chunk, in cells.
+pub const CHUNK_SIZE: i32 = 16;
+const CHUNK_VOLUME: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ChunkKey {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl ChunkKey {
+    pub fn of(cell: WorldCell) -> Self {
+        Self {
+            x: cell.x.div_euclid(CHUNK_SIZE),
+            y: cell.y.div_euclid(CHUNK_SIZE),
+            z: cell.z.div_euclid(CHUNK_SIZE),
+        }
+    }
+
+    /// The chunk's lowest cell.
+    pub fn origin(&self) -> WorldCell {
+        WorldCell {
+            x: self.x * CHUNK_SIZE,
+            y: self.y * CHUNK_SIZE,
+            z: self.z * CHUNK_SIZE,
+        }
+    }
+}
+
+fn local_index(cell: WorldCell) -> usize {
+    let x = cell.x.rem_euclid(CHUNK_SIZE);
+    let y = cell.y.rem_euclid(CHUNK_SIZE);
+    let z = cell.z.rem_euclid(CHUNK_SIZE);
+    ((z * CHUNK_SIZE + y) * CHUNK_SIZE + x) as usize
+}
+
+#[derive(Debug, Clone)]
+struct Chunk<T> {
+    cells: Box<[Option<T>]>,
+    filled: usize,
+}
+
+impl<T: Copy> Chunk<T> {
+    fn new() -> Self {
+        Self {
+            cells: vec![None; CHUNK_VOLUME].into_boxed_slice(),
+            filled: 0,
+        }
+    }
+}
+
+/// Sparse map of dense `CHUNK_SIZE`³ chunks. Only chunks holding at least
+/// one cell are allocated, so memory follows the built volume rather than
+/// the extent of the world.
+#[derive(Debug, Clone)]
+pub struct ChunkMap<T> {
+    chunks: HashMap<ChunkKey, Chunk<T>>,
+    len: usize,
+}
+
+impl<T: Copy> Default for ChunkMap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Copy> ChunkMap<T> {
+    pub fn new() -> Self {
+        Self { chunks: HashMap::new(), len: 0 }
+    }
+
+    pub fn get(&self, cell: WorldCell) -> Option<T> {
+        self.chunks
+            .get(&ChunkKey::of(cell))
+            .and_then(|chunk| chunk.cells[local_index(cell)])
+    }
+
+    pub fn contains(&self, cell: WorldCell) -> bool {
+        self.get(cell).is_some()
+    }
+
+    /// Stores `value` at `cell`, returning the value it replaced.
+    pub fn insert(&mut self, cell: WorldCell, value: T) -> Option<T> {
+        let chunk = self.chunks.entry(ChunkKey::of(cell)).or_insert_with(Chunk::new);
+        let old = chunk.cells[local_index(cell)].replace(value);
+        if old.is_none() {
+            chunk.filled += 1;
+            self.len += 1;
+        }
+        old
+    }
+
+    /// Clears `cell`, freeing its chunk once the chunk is empty.
+    pub fn remove(&mut self, cell: WorldCell) -> Option<T> {
+        let key = ChunkKey::of(cell);
+        let chunk = self.chunks.get_mut(&key)?;
+        let old = chunk.cells[local_index(cell)].take()?;
+        chunk.filled -= 1;
+        self.len -= 1;
+        if chunk.filled == 0 {
+            self.chunks.remove(&key);
+        }
+        Some(old)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of allocated chunks.
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    /// Every filled cell, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (WorldCell, T)> + '_ {
+        self.chunks.iter().flat_map(|(key, chunk)| {
+            let origin = key.origin();
+            chunk.cells.iter().enumerate().filter_map(move |(i, v)| {
+                v.map(|v| (offset_of(origin, i), v))
+            })
+        })
+    }
+
+    /// Filled cells inside `region` (inclusive on both ends), ordered by
+    /// `(z, y, x)`. Only the chunks overlapping the region are visited.
+    pub fn region(&self, region: &Bounds) -> Vec<(WorldCell, T)> {
+        let lo = ChunkKey::of(WorldCell { x: region.x[0], y: region.y[0], z: region.z[0] });
+        let hi = ChunkKey::of(WorldCell { x: region.x[1], y: region.y[1], z: region.z[1] });
+
+        let mut found = Vec::new();
+        let mut visit = |key: ChunkKey, chunk: &Chunk<T>| {
+            let origin = key.origin();
+            for (i, v) in chunk.cells.iter().enumerate() {
+                let Some(v) = *v else { continue };
+                let cell = offset_of(origin, i);
+                if in_region(region, cell) {
+                    found.push((cell, v));
+                }
+            }
+        };
+
+        // Scanning the allocated chunks beats probing a huge empty box.
+        let span = |a: i32, b: i32| (b as i64 - a as i64 + 1).max(0) as u128;
+        let boxed = span(lo.x, hi.x) * span(lo.y, hi.y) * span(lo.z, hi.z);
+        if boxed > self.chunks.len() as u128 {
+            for (key, chunk) in &self.chunks {
+                if (lo.x..=hi.x).contains(&key.x)
+                    && (lo.y..=hi.y).contains(&key.y)
+                    && (lo.z..=hi.z).contains(&key.z)
+                {
+                    visit(*key, chunk);
+                }
+            }
+        } else {
+            for z in lo.z..=hi.z {
+                for y in lo.y..=hi.y {
+                    for x in lo.x..=hi.x {
+                        let key = ChunkKey { x, y, z };
+                        if let Some(chunk) = self.chunks.get(&key) {
+                            visit(key, chunk);
+                        }
+                    }
+                }
+            }
+        }
+
+        found.sort_by_key(|(c, _)| (c.z, c.y, c.x));
+        found
+    }
+}
+
+fn offset_of(origin: WorldCell, index: usize) -> WorldCell {
+    let i = index as i32;
+    WorldCell {
+        x: origin.x + i % CHUNK_SIZE,
+        y: origin.y + (i / CHUNK_SIZE) % CHUNK_SIZE,
+        z: origin.z + i / (CHUNK_SIZE * CHUNK_SIZE),
+    }
+}
+
+fn in_region(region: &Bounds, c: WorldCell) -> bool {
+    (region.x[0]..=region.x[1]).contains(&c.x)
+        && (region.y[0]..=region.y[1]).contains(&c.y)
+        && (region.z[0]..=region.z[1]).contains(&c.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cell(x: i32, y: i32, z: i32) -> WorldCell {
+        WorldCell { x, y, z }
+    }
+
+    #[test]
+    fn negative_cells_land_in_their_own_chunks() {
+        let mut map = ChunkMap::new();
+        assert_eq!(map.insert(cell(-1, 0, 0), 1), None);
+        assert_eq!(map.insert(cell(0, 0, 0), 2), None);
+        assert_eq!(map.insert(cell(-16, -17, 15), 3), None);
+
+        assert_eq!(map.chunk_count(), 3);
+        assert_eq!(map.get(cell(-1, 0, 0)), Some(1));
+        assert_eq!(map.get(cell(-16, -17, 15)), Some(3));
+        assert_eq!(map.insert(cell(0, 0, 0), 4), Some(2));
+        assert_eq!(map.len(), 3);
+
+        let mut all: Vec<_> = map.iter().collect();
+        all.sort_by_key(|(c, _)| (c.x, c.y, c.z));
+        assert_eq!(all[0], (cell(-16, -17, 15), 3));
+    }
+
+    #[test]
+    fn empty_chunks_are_freed() {
+        let mut map = ChunkMap::new();
+        map.insert(cell(3, 3, 3), 'a');
+        map.insert(cell(4, 3, 3), 'b');
+
+        assert_eq!(map.remove(cell(3, 3, 3)), Some('a'));
+        assert_eq!(map.remove(cell(3, 3, 3)), None);
+        assert_eq!(map.chunk_count(), 1);
+        map.remove(cell(4, 3, 3));
+        assert_eq!(map.chunk_count(), 0);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn region_crosses_chunk_borders() {
+        let mut map = ChunkMap::new();
+        for x in -20..20 {
+            map.insert(cell(x, 0, 0), x);
+        }
+        map.insert(cell(0, 1, 0), 100);
+
+        let region = Bounds { x: [-17, 16], y: [0, 0], z: [0, 0] };
+        let found = map.region(&region);
+        assert_eq!(found.len(), 34);
+        assert_eq!(found.first(), Some(&(cell(-17, 0, 0), -17)));
+        assert_eq!(found.last(), Some(&(cell(16, 0, 0), 16)));
+
+        let huge = Bounds { x: [i32::MIN, i32::MAX], y: [1, 1], z: [i32::MIN, i32::MAX] };
+        assert_eq!(map.region(&huge), vec![(cell(0, 1, 0), 100)]);
+    }
+}
@@ -1,15 +1,35 @@
+use crate::rational::Rational;
+use crate::units::UnitScale;
+use glam::{Mat3, Quat, Vec3};
 use serde::Deserialize;
 
-#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
 #[serde(rename_all = "snake_case")]
 pub enum ConnectorType {
     Stud,
     Tube,
     Pin,
+    PinHole,
+    Axle,
     AxleHole,
+    Clip,
+    Bar,
+    Ball,
+    Socket,
+    Hinge,
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq)]
+/// How a joint lets the two mated parts move relative to each other.
+/// Ordered from most to least constrained.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Freedom {
+    Fixed,
+    /// Rotation about the connector's own axis, i.e. its `dir`.
+    Rotational,
+    Spherical,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Direction {
     #[serde(rename = "+x")]
     PosX,
@@ -35,19 +55,172 @@ pub struct Connector {
     #[serde(rename = "type")]
     pub kind: ConnectorType,
     pub pos: Position,
-    pub dir: Direction,
+    pub dir: Facing,
 }
 
-#[derive(Debug, Deserialize)]
+/// Which way a connector faces. Besides the axis shorthand (`"+z"`), a
+/// connector can give its normal, optionally with an `up` vector fixing the
+/// twist about it, or a quaternion `[x, y, z, w]` taking +Z to the normal.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(untagged)]
+pub enum Facing {
+    Axis(Direction),
+    Normal {
+        normal: [f32; 3],
+        #[serde(default)]
+        up: Option<[f32; 3]>,
+    },
+    Quat {
+        quat: [f32; 4],
+    },
+}
+
+impl Facing {
+    /// Rotation taking local +Z to the connector normal (and local +Y to
+    /// `up`, when given).
+    pub fn rotation(self) -> Quat {
+        match self {
+            Facing::Axis(d) => Quat::from_rotation_arc(Vec3::Z, d.normal()),
+            Facing::Normal { normal, up: None } => {
+                Quat::from_rotation_arc(Vec3::Z, Vec3::from(normal).normalize())
+            }
+            Facing::Normal { normal, up: Some(up) } => {
+                let n = Vec3::from(normal).normalize();
+                let u = Vec3::from(up).reject_from(n).normalize();
+                Quat::from_mat3(&Mat3::from_cols(u.cross(n), u, n))
+            }
+            Facing::Quat { quat } => Quat::from_array(quat).normalize(),
+        }
+    }
+
+    pub fn normal(self) -> Vec3 {
+        match self {
+            Facing::Axis(d) => d.normal(),
+            _ => self.rotation() * Vec3::Z,
+        }
+    }
+
+    fn validate(self) -> Result<(), String> {
+        match self {
+            Facing::Axis(_) => Ok(()),
+            Facing::Normal { normal, up } => {
+                let n = Vec3::from(normal);
+                if !n.is_finite() || n.length_squared() < 1e-12 {
+                    return Err(format!("connector normal must be non-zero: {:?}", normal));
+                }
+                if let Some(up) = up {
+                    if Vec3::from(up).reject_from(n).length_squared() < 1e-12 {
+                        return Err(format!(
+                            "connector up {:?} is parallel to its normal {:?}",
+                            up, normal
+                        ));
+                    }
+                }
+                Ok(())
+            }
+            Facing::Quat { quat } => {
+                let q = Quat::from_array(quat);
+                if !q.is_finite() || q.length_squared() < 1e-12 {
+                    return Err(format!("connector quaternion must be non-zero: {:?}", quat));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Connector position in lattice cells. Components may be fractions, e.g.
+/// `"1/2"` for a jumper stud between two cells or `"1/3"` for a pin hole
+/// between plate levels.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Position {
-    pub x: i32,
-    pub y: i32,
-    pub z: i32,
+    pub x: Rational,
+    pub y: Rational,
+    pub z: Rational,
+}
+
+impl Position {
+    pub fn new(x: impl Into<Rational>, y: impl Into<Rational>, z: impl Into<Rational>) -> Self {
+        Self { x: x.into(), y: y.into(), z: z.into() }
+    }
+
+    /// Scales the position to world units, e.g. millimetres with
+    /// `units::lego::SCALE_MM`.
+    pub fn to_world(self, scale: UnitScale) -> [f32; 3] {
+        scale.cell_to_world(self.x.to_f32(), self.y.to_f32(), self.z.to_f32())
+    }
+}
+
+impl ConnectorType {
+    /// Whether this kind accepts `other` as its counterpart.
+    pub fn mates_with(self, other: ConnectorType) -> bool {
+        use ConnectorType::*;
+        matches!(
+            (self, other),
+            (Stud, Tube)
+                | (Tube, Stud)
+                | (Pin, PinHole)
+                | (PinHole, Pin)
+                | (Axle, AxleHole)
+                | (AxleHole, Axle)
+                | (Axle, PinHole)
+                | (PinHole, Axle)
+                | (Clip, Bar)
+                | (Bar, Clip)
+                | (Ball, Socket)
+                | (Socket, Ball)
+                | (Hinge, Hinge)
+        )
+    }
+
+    /// Male kinds stick out of the part into the neighbouring cell, which
+    /// another part may only fill if it takes the connector.
+    pub fn protrudes(self) -> bool {
+        use ConnectorType::*;
+        matches!(self, Stud | Pin | Axle | Bar | Ball)
+    }
+
+    pub fn freedom(self) -> Freedom {
+        use ConnectorType::*;
+        match self {
+            Stud | Tube | Axle | AxleHole => Freedom::Fixed,
+            Pin | PinHole | Clip | Bar | Hinge => Freedom::Rotational,
+            Ball | Socket => Freedom::Spherical,
+        }
+    }
+
+    /// Freedom of the joint this kind forms with `other`, or `None` if the
+    /// two do not mate. The looser side wins: an axle turns freely in a pin
+    /// hole.
+    pub fn joint_freedom(self, other: ConnectorType) -> Option<Freedom> {
+        if self.mates_with(other) {
+            Some(self.freedom().max(other.freedom()))
+        } else {
+            None
+        }
+    }
+}
+
+impl Direction {
+    pub fn normal(self) -> Vec3 {
+        match self {
+            Direction::PosX => Vec3::X,
+            Direction::NegX => Vec3::NEG_X,
+            Direction::PosY => Vec3::Y,
+            Direction::NegY => Vec3::NEG_Y,
+            Direction::PosZ => Vec3::Z,
+            Direction::NegZ => Vec3::NEG_Z,
+        }
+    }
 }
 
 impl ConnectorFile {
     pub fn validate(&self) -> Result<(), String> {
-        // If it deserializes, type and direction are already valid.
+        // Types and axis directions are checked by deserializing; explicit
+        // normals and quaternions still need to describe a direction.
+        for c in &self.connectors {
+            c.dir.validate()?;
+        }
         Ok(())
     }
 }
@@ -0,0 +1,278 @@
+use crate::orientation::Orientation;
+use crate::world::{cell_bounds, PartInstance, PartInstanceId, World, WorldCell};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// A part that was built, but not where the blueprint has it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Misplaced {
+    pub blueprint: PartInstanceId,
+    pub built: PartInstanceId,
+    pub part: String,
+    /// Built offset minus blueprint offset.
+    pub displacement: WorldCell,
+    pub expected: Orientation,
+    pub actual: Orientation,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WrongColor {
+    pub blueprint: PartInstanceId,
+    pub built: PartInstanceId,
+    pub expected: String,
+    pub actual: Option<String>,
+}
+
+/// Differences between a blueprint and what was built. Ids on each side
+/// refer to their own world.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorldDiff {
+    /// Pairs in place, as `(blueprint, built)`.
+    pub matched: Vec<(PartInstanceId, PartInstanceId)>,
+    pub misplaced: Vec<Misplaced>,
+    /// Matched or misplaced pairs whose color is off.
+    pub wrong_color: Vec<WrongColor>,
+    /// Blueprint instances with nothing built for them.
+    pub missing: Vec<PartInstanceId>,
+    /// Built instances the blueprint does not call for.
+    pub extra: Vec<PartInstanceId>,
+}
+
+impl WorldDiff {
+    /// True when the build matches the blueprint exactly.
+    pub fn is_empty(&self) -> bool {
+        self.misplaced.is_empty()
+            && self.wrong_color.is_empty()
+            && self.missing.is_empty()
+            && self.extra.is_empty()
+    }
+}
+
+/// Matches instances by what they are, never by id.
+///
+/// Pairs of the same part filling the same cells match first, so turning a
+/// symmetric part in place is not a difference. The remaining instances of
+/// each part pair up nearest first as misplaced, but only where the two
+/// footprints' bounding boxes overlap or touch: a part built across the
+/// model from its spot is an extra one, and the planned one is missing.
+/// Whatever is left is missing or extra. A blueprint instance without a
+/// color accepts any color.
+pub fn diff_worlds(blueprint: &World, built: &World) -> WorldDiff {
+    let mut diff = WorldDiff::default();
+
+    let mut wanted: BTreeMap<(&str, Vec<CellKey>), Vec<PartInstanceId>> = BTreeMap::new();
+    for (id, inst) in blueprint.instances() {
+        wanted.entry((inst.part.as_str(), sorted_cells(blueprint, id))).or_default().push(id);
+    }
+
+    let mut unmatched_built = Vec::new();
+    for (id, inst) in built.instances() {
+        let key = (inst.part.as_str(), sorted_cells(built, id));
+        match wanted.get_mut(&key).filter(|ids| !ids.is_empty()) {
+            Some(ids) => {
+                let b = ids.remove(0);
+                diff.matched.push((b, id));
+            }
+            None => unmatched_built.push(id),
+        }
+    }
+
+    let unmatched_blueprint: BTreeSet<_> = wanted.into_values().flatten().collect();
+
+    let mut candidates = Vec::new();
+    for &b in &unmatched_blueprint {
+        let want = blueprint.instance(b).expect("listed above");
+        for &a in &unmatched_built {
+            let got = built.instance(a).expect("listed above");
+            if got.part == want.part && within_reach(blueprint, b, built, a) {
+                candidates.push((distance(want, got), b, a));
+            }
+        }
+    }
+    candidates.sort();
+
+    let mut taken_blueprint = BTreeSet::new();
+    let mut taken_built = BTreeSet::new();
+    for (_, b, a) in candidates {
+        if taken_blueprint.contains(&b) || taken_built.contains(&a) {
+            continue;
+        }
+        taken_blueprint.insert(b);
+        taken_built.insert(a);
+
+        let want = blueprint.instance(b).expect("listed above");
+        let got = built.instance(a).expect("listed above");
+        diff.misplaced.push(Misplaced {
+            blueprint: b,
+            built: a,
+            part: want.part.clone(),
+            displacement: WorldCell {
+                x: got.offset.x - want.offset.x,
+                y: got.offset.y - want.offset.y,
+                z: got.offset.z - want.offset.z,
+            },
+            expected: want.orientation,
+            actual: got.orientation,
+        });
+    }
+    diff.misplaced.sort_by_key(|m| m.blueprint);
+
+    let pairs = diff
+        .matched
+        .iter()
+        .copied()
+        .chain(diff.misplaced.iter().map(|m| (m.blueprint, m.built)));
+    for (b, a) in pairs {
+        let want = blueprint.instance(b).expect("listed above");
+        let got = built.instance(a).expect("listed above");
+        if let Some(expected) = &want.color {
+            if got.color.as_ref() != Some(expected) {
+                diff.wrong_color.push(WrongColor {
+                    blueprint: b,
+                    built: a,
+                    expected: expected.clone(),
+                    actual: got.color.clone(),
+                });
+            }
+        }
+    }
+    diff.wrong_color.sort_by_key(|w| w.blueprint);
+    diff.matched.sort();
+
+    diff.missing = unmatched_blueprint
+        .into_iter()
+        .filter(|b| !taken_blueprint.contains(b))
+        .collect();
+    diff.extra = unmatched_built
+        .into_iter()
+        .filter(|a| !taken_built.contains(a))
+        .collect();
+
+    diff
+}
+
+/// A cell as `(z, y, x)`, ordered for comparing footprints.
+type CellKey = (i32, i32, i32);
+
+fn sorted_cells(world: &World, id: PartInstanceId) -> Vec<CellKey> {
+    let mut cells: Vec<_> = world
+        .instance_cells(id)
+        .unwrap_or(&[])
+        .iter()
+        .map(|c| (c.z, c.y, c.x))
+        .collect();
+    cells.sort();
+    cells
+}
+
+/// Whether the bounding boxes of the two footprints overlap or share a face,
+/// edge or corner.
+fn within_reach(blueprint: &World, b: PartInstanceId, built: &World, a: PartInstanceId) -> bool {
+    let (Some(want), Some(got)) = (footprint_box(blueprint, b), footprint_box(built, a)) else {
+        return false;
+    };
+    (0..3).all(|i| want[i][0] <= got[i][1] + 1 && got[i][0] <= want[i][1] + 1)
+}
+
+/// Inclusive `[min, max]` per axis, as `[x, y, z]`.
+fn footprint_box(world: &World, id: PartInstanceId) -> Option<[[i32; 2]; 3]> {
+    let b = cell_bounds(world.instance_cells(id)?.iter())?;
+    Some([b.x, b.y, b.z])
+}
+
+/// Squared offset distance, with a turn counted as a small extra step so
+/// that, among equally near candidates, the one facing the same way wins.
+fn distance(a: &PartInstance, b: &PartInstance) -> i64 {
+    let dx = (a.offset.x - b.offset.x) as i64;
+    let dy = (a.offset.y - b.offset.y) as i64;
+    let dz = (a.offset.z - b.offset.z) as i64;
+    let turned = (a.orientation != b.orientation) as i64;
+    2 * (dx * dx + dy * dy + dz * dz) + turned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::catalog::Catalog;
+
+    fn at(x: i32, y: i32, z: i32) -> WorldCell {
+        WorldCell { x, y, z }
+    }
+
+    #[test]
+    fn matches_by_content_not_id() {
+        let catalog = Catalog::load_dir("assets/parts").unwrap();
+        let brick = catalog.get("lego:3001").unwrap();
+        let block = catalog.get("lego:3003").unwrap();
+        let slim = catalog.get("lego:3004").unwrap();
+
+        let mut plan = World::new();
+        let p_base = plan.place_part(brick, at(0, 0, 0)).unwrap();
+        let p_block = plan.place_part(block, at(0, 0, 3)).unwrap();
+        let p_slim = plan.place_part(slim, at(10, 0, 0)).unwrap();
+        let p_far = plan.place_part(block, at(20, 0, 0)).unwrap();
+        plan.set_color(p_base, Some("red".to_string())).unwrap();
+
+        // Built in another order, the 2x2 turned in place, the 1x2 one stud
+        // off and turned, the last 2x2 nowhere near its spot.
+        let mut site = World::new();
+        let s_far = site.place_part(block, at(40, 40, 0)).unwrap();
+        let s_slim = site
+            .place_part_oriented(slim, at(11, 0, 0), Orientation::Deg90)
+            .unwrap();
+        let s_block = site
+            .place_part_oriented(block, at(0, 0, 3), Orientation::Deg180)
+            .unwrap();
+        let s_base = site.place_part(brick, at(0, 0, 0)).unwrap();
+        site.set_color(s_base, Some("blue".to_string())).unwrap();
+
+        let diff = diff_worlds(&plan, &site);
+
+        assert_eq!(diff.matched, vec![(p_base, s_base), (p_block, s_block)]);
+        assert_eq!(diff.misplaced.len(), 1);
+        assert_eq!(
+            diff.misplaced[0],
+            Misplaced {
+                blueprint: p_slim,
+                built: s_slim,
+                part: "lego:3004".to_string(),
+                displacement: at(1, 0, 0),
+                expected: Orientation::Deg0,
+                actual: Orientation::Deg90,
+            }
+        );
+        // Too far off to be the planned 2x2 moved: one missing, one extra.
+        assert_eq!(diff.missing, vec![p_far]);
+        assert_eq!(diff.extra, vec![s_far]);
+
+        assert_eq!(diff.wrong_color.len(), 1);
+        assert_eq!(diff.wrong_color[0].actual.as_deref(), Some("blue"));
+        assert!(!diff.is_empty());
+
+        assert!(diff_worlds(&plan, &plan).is_empty());
+    }
+
+    #[test]
+    fn unmatched_parts_are_missing_or_extra() {
+        let catalog = Catalog::load_dir("assets/parts").unwrap();
+        let mut plan = World::new();
+        let wanted = plan.place_part(catalog.get("lego:3001").unwrap(), at(0, 0, 0)).unwrap();
+
+        let mut site = World::new();
+        let built = site.place_part(catalog.get("lego:3002").unwrap(), at(0, 0, 0)).unwrap();
+
+        let diff = diff_worlds(&plan, &site);
+        assert_eq!(diff.missing, vec![wanted]);
+        assert_eq!(diff.extra, vec![built]);
+        assert!(diff.matched.is_empty() && diff.misplaced.is_empty());
+
+        // Right beside its spot still counts as moved; one stud further does not.
+        let beside = site.place_part(catalog.get("lego:3001").unwrap(), at(2, 0, 0)).unwrap();
+        let diff = diff_worlds(&plan, &site);
+        assert_eq!((diff.misplaced[0].blueprint, diff.misplaced[0].built), (wanted, beside));
+
+        site.move_part(beside, at(3, 0, 0)).unwrap();
+        let diff = diff_worlds(&plan, &site);
+        assert!(diff.misplaced.is_empty());
+        assert_eq!(diff.extra, vec![built, beside]);
+    }
+}
@@ -0,0 +1,202 @@
+use crate::connectors::Freedom;
+use crate::world::PartInstanceId;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+/// One mated connector pair, as indices into each part's connector list.
+/// `local` belongs to the instance the edge is looked up from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mate {
+    pub local: usize,
+    pub remote: usize,
+    pub freedom: Freedom,
+}
+
+/// Undirected graph of placed instances, with an edge wherever at least one
+/// connector pair mates.
+#[derive(Debug, Default)]
+pub struct ConnectionGraph {
+    edges: BTreeMap<PartInstanceId, BTreeMap<PartInstanceId, Vec<Mate>>>,
+}
+
+impl ConnectionGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_instance(&mut self, id: PartInstanceId) {
+        self.edges.entry(id).or_default();
+    }
+
+    pub fn connect(
+        &mut self,
+        a: (PartInstanceId, usize),
+        b: (PartInstanceId, usize),
+        freedom: Freedom,
+    ) {
+        self.edges
+            .entry(a.0)
+            .or_default()
+            .entry(b.0)
+            .or_default()
+            .push(Mate { local: a.1, remote: b.1, freedom });
+        self.edges
+            .entry(b.0)
+            .or_default()
+            .entry(a.0)
+            .or_default()
+            .push(Mate { local: b.1, remote: a.1, freedom });
+    }
+
+    /// Drops an instance together with every connection it takes part in.
+    pub fn remove_instance(&mut self, id: PartInstanceId) {
+        if let Some(neighbors) = self.edges.remove(&id) {
+            for other in neighbors.keys() {
+                if let Some(n) = self.edges.get_mut(other) {
+                    n.remove(&id);
+                }
+            }
+        }
+    }
+
+    pub fn contains(&self, id: PartInstanceId) -> bool {
+        self.edges.contains_key(&id)
+    }
+
+    pub fn instance_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    /// Number of connected instance pairs, regardless of how many connectors
+    /// each pair shares.
+    pub fn connection_count(&self) -> usize {
+        self.edges.values().map(|n| n.len()).sum::<usize>() / 2
+    }
+
+    pub fn neighbors(&self, id: PartInstanceId) -> Vec<PartInstanceId> {
+        self.edges
+            .get(&id)
+            .map(|n| n.keys().copied().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn mates(&self, a: PartInstanceId, b: PartInstanceId) -> &[Mate] {
+        self.edges
+            .get(&a)
+            .and_then(|n| n.get(&b))
+            .map(|m| m.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Freedom left between two connected instances. The most constrained
+    /// mate decides, so a brick held by one stud and one pin is fixed.
+    pub fn joint_freedom(&self, a: PartInstanceId, b: PartInstanceId) -> Option<Freedom> {
+        self.mates(a, b).iter().map(|m| m.freedom).min()
+    }
+
+    pub fn is_rigid(&self, a: PartInstanceId, b: PartInstanceId) -> bool {
+        self.joint_freedom(a, b) == Some(Freedom::Fixed)
+    }
+
+    pub fn is_connector_mated(&self, id: PartInstanceId, index: usize) -> bool {
+        self.edges
+            .get(&id)
+            .map(|n| n.values().flatten().any(|m| m.local == index))
+            .unwrap_or(false)
+    }
+
+    /// Groups of mutually reachable instances, each sorted, ordered by their
+    /// smallest id.
+    pub fn connected_components(&self) -> Vec<Vec<PartInstanceId>> {
+        let mut seen = BTreeSet::new();
+        let mut components = Vec::new();
+
+        for &start in self.edges.keys() {
+            if !seen.insert(start) {
+                continue;
+            }
+
+            let mut component = vec![start];
+            let mut queue = VecDeque::from([start]);
+            while let Some(id) = queue.pop_front() {
+                for &next in self.edges[&id].keys() {
+                    if seen.insert(next) {
+                        component.push(next);
+                        queue.push_back(next);
+                    }
+                }
+            }
+
+            component.sort();
+            components.push(component);
+        }
+
+        components
+    }
+
+    /// True when every instance is reachable from every other one. An empty
+    /// graph is not a model, so it is not one piece either.
+    pub fn is_one_piece(&self) -> bool {
+        self.connected_components().len() == 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(n: u64) -> PartInstanceId {
+        PartInstanceId(n)
+    }
+
+    #[test]
+    fn connect_is_symmetric() {
+        let mut g = ConnectionGraph::new();
+        g.connect((id(1), 0), (id(2), 5), Freedom::Fixed);
+
+        assert_eq!(g.neighbors(id(1)), vec![id(2)]);
+        assert_eq!(g.neighbors(id(2)), vec![id(1)]);
+        assert_eq!(
+            g.mates(id(2), id(1)),
+            &[Mate { local: 5, remote: 0, freedom: Freedom::Fixed }]
+        );
+        assert_eq!(g.connection_count(), 1);
+    }
+
+    #[test]
+    fn components_include_isolated_instances() {
+        let mut g = ConnectionGraph::new();
+        for n in 1..=4 {
+            g.add_instance(id(n));
+        }
+        g.connect((id(1), 0), (id(3), 0), Freedom::Fixed);
+        g.connect((id(3), 1), (id(4), 0), Freedom::Fixed);
+
+        assert_eq!(
+            g.connected_components(),
+            vec![vec![id(1), id(3), id(4)], vec![id(2)]]
+        );
+        assert!(!g.is_one_piece());
+
+        g.connect((id(2), 0), (id(4), 1), Freedom::Fixed);
+        assert!(g.is_one_piece());
+
+        g.remove_instance(id(3));
+        assert_eq!(g.neighbors(id(4)), vec![id(2)]);
+        assert_eq!(
+            g.connected_components(),
+            vec![vec![id(1)], vec![id(2), id(4)]]
+        );
+    }
+
+    #[test]
+    fn most_constrained_mate_sets_joint_freedom() {
+        let mut g = ConnectionGraph::new();
+        g.connect((id(1), 0), (id(2), 0), Freedom::Rotational);
+        assert_eq!(g.joint_freedom(id(1), id(2)), Some(Freedom::Rotational));
+        assert!(!g.is_rigid(id(2), id(1)));
+
+        g.connect((id(1), 1), (id(2), 1), Freedom::Fixed);
+        assert!(g.is_rigid(id(1), id(2)));
+        assert_eq!(g.joint_freedom(id(1), id(3)), None);
+    }
+}
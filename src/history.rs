@@ -0,0 +1,210 @@
+use crate::world::{InstanceRecord, PartInstanceId, WorldCell};
+use std::collections::VecDeque;
+
+/// Undo steps kept by a new world.
+pub const DEFAULT_HISTORY_LIMIT: usize = 256;
+
+/// A reversible world edit, carrying enough state to apply it either way.
+#[derive(Debug, Clone)]
+pub enum Command {
+    Place { id: PartInstanceId, record: InstanceRecord },
+    Remove { id: PartInstanceId, record: InstanceRecord },
+    Move { id: PartInstanceId, from: WorldCell, to: WorldCell },
+    Recolor { id: PartInstanceId, from: Option<String>, to: Option<String> },
+    Relabel { id: PartInstanceId, from: Option<String>, to: Option<String> },
+}
+
+impl Command {
+    pub fn inverse(&self) -> Command {
+        match self {
+            Command::Place { id, record } => Command::Remove { id: *id, record: record.clone() },
+            Command::Remove { id, record } => Command::Place { id: *id, record: record.clone() },
+            Command::Move { id, from, to } => Command::Move { id: *id, from: *to, to: *from },
+            Command::Recolor { id, from, to } => Command::Recolor {
+                id: *id,
+                from: to.clone(),
+                to: from.clone(),
+            },
+            Command::Relabel { id, from, to } => Command::Relabel {
+                id: *id,
+                from: to.clone(),
+                to: from.clone(),
+            },
+        }
+    }
+}
+
+/// History state to rewind to, taken by [`History::checkpoint`].
+#[derive(Debug)]
+pub(crate) struct Checkpoint {
+    open_len: usize,
+    undone: Vec<Vec<Command>>,
+}
+
+/// Undo and redo stacks of command groups. Each group is one undo step.
+#[derive(Debug)]
+pub struct History {
+    done: VecDeque<Vec<Command>>,
+    undone: Vec<Vec<Command>>,
+    open: Vec<Command>,
+    depth: usize,
+    limit: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new(DEFAULT_HISTORY_LIMIT)
+    }
+}
+
+impl History {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            done: VecDeque::new(),
+            undone: Vec::new(),
+            open: Vec::new(),
+            depth: 0,
+            limit,
+        }
+    }
+
+    /// Records a command that was just applied. A fresh edit makes the
+    /// redo stack meaningless, so it is dropped.
+    pub fn record(&mut self, command: Command) {
+        self.undone.clear();
+        if self.depth > 0 {
+            self.open.push(command);
+        } else {
+            self.push_done(vec![command]);
+        }
+    }
+
+    /// Starts collecting commands into one undo step. Groups nest; only the
+    /// outermost `end_group` closes the step.
+    pub fn begin_group(&mut self) {
+        self.depth += 1;
+    }
+
+    pub fn end_group(&mut self) {
+        if self.depth == 0 {
+            return;
+        }
+        self.depth -= 1;
+        if self.depth == 0 && !self.open.is_empty() {
+            let group = std::mem::take(&mut self.open);
+            self.push_done(group);
+        }
+    }
+
+    pub fn in_group(&self) -> bool {
+        self.depth > 0
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Caps the number of undo steps, dropping the oldest ones beyond it.
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+        self.trim();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.done.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+
+    pub fn undo_len(&self) -> usize {
+        self.done.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.done.clear();
+        self.undone.clear();
+        self.open.clear();
+        self.depth = 0;
+    }
+
+    /// Marks the current state, e.g. at the start of a transaction.
+    pub(crate) fn checkpoint(&self) -> Checkpoint {
+        Checkpoint { open_len: self.open.len(), undone: self.undone.clone() }
+    }
+
+    /// Takes back the open group's commands recorded since `checkpoint`,
+    /// e.g. to roll a failed transaction back, and restores the redo stack
+    /// those commands cleared.
+    pub(crate) fn rewind(&mut self, checkpoint: Checkpoint) -> Vec<Command> {
+        self.undone = checkpoint.undone;
+        self.open.split_off(checkpoint.open_len)
+    }
+
+    pub(crate) fn take_undo(&mut self) -> Option<Vec<Command>> {
+        self.done.pop_back()
+    }
+
+    pub(crate) fn take_redo(&mut self) -> Option<Vec<Command>> {
+        self.undone.pop()
+    }
+
+    pub(crate) fn push_undone(&mut self, group: Vec<Command>) {
+        self.undone.push(group);
+    }
+
+    pub(crate) fn push_done(&mut self, group: Vec<Command>) {
+        self.done.push_back(group);
+        self.trim();
+    }
+
+    fn trim(&mut self) {
+        while self.done.len() > self.limit {
+            self.done.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recolor(n: u64) -> Command {
+        Command::Recolor {
+            id: PartInstanceId(n),
+            from: None,
+            to: Some("red".to_string()),
+        }
+    }
+
+    #[test]
+    fn groups_nest_into_one_step() {
+        let mut h = History::default();
+        h.begin_group();
+        h.record(recolor(1));
+        h.begin_group();
+        h.record(recolor(2));
+        h.end_group();
+        assert_eq!(h.undo_len(), 0);
+        h.end_group();
+
+        assert_eq!(h.undo_len(), 1);
+        assert_eq!(h.take_undo().map(|g| g.len()), Some(2));
+    }
+
+    #[test]
+    fn limit_drops_oldest_steps() {
+        let mut h = History::new(2);
+        for n in 1..=3 {
+            h.record(recolor(n));
+        }
+        assert_eq!(h.undo_len(), 2);
+
+        let oldest = h.done.front().unwrap();
+        assert!(matches!(oldest[0], Command::Recolor { id: PartInstanceId(2), .. }));
+
+        h.set_limit(0);
+        assert!(!h.can_undo());
+    }
+}
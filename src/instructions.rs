@@ -0,0 +1,373 @@
+use crate::assembly::{plan_assembly, AssemblyPlan};
+use crate::units::lego;
+use crate::visuals::{OffscreenRenderer, Solid};
+use crate::world::{PartInstanceId, World};
+use glam::Vec3;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+#[derive(Debug, Clone)]
+pub struct InstructionOptions {
+    /// Most parts added in one step.
+    pub max_parts_per_step: usize,
+    /// Step image size in pixels.
+    pub image_size: (u32, u32),
+    /// Render a PNG per step. Without it the booklet is text only, e.g. on
+    /// machines with no GPU adapter.
+    pub render_images: bool,
+}
+
+impl Default for InstructionOptions {
+    fn default() -> Self {
+        Self {
+            max_parts_per_step: 4,
+            image_size: (800, 600),
+            render_images: true,
+        }
+    }
+}
+
+/// Parts of one kind and color added in a step.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartCount {
+    pub part: String,
+    pub color: Option<String>,
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstructionStep {
+    /// Instances added in this step, in build order.
+    pub added: Vec<PartInstanceId>,
+    /// The same instances tallied by part and color.
+    pub parts: Vec<PartCount>,
+}
+
+/// Splits a build order into steps of at most `max_parts` parts. A step also
+/// ends where the order moves on to a new layer or into or out of a placed
+/// submodel, so parts at different heights or of different sub-assemblies
+/// are not mixed.
+pub fn group_steps(world: &World, plan: &AssemblyPlan, max_parts: usize) -> Vec<InstructionStep> {
+    let max_parts = max_parts.max(1);
+    let mut steps: Vec<Vec<PartInstanceId>> = Vec::new();
+    let mut last = None;
+
+    for &id in &plan.order {
+        let unit = world.instance(id).and_then(|i| i.submodel.as_ref()).map(|s| s.id);
+        let key = (world.bottom_layer(id), unit);
+        match steps.last_mut() {
+            Some(step) if step.len() < max_parts && last == Some(key) => step.push(id),
+            _ => steps.push(vec![id]),
+        }
+        last = Some(key);
+    }
+
+    steps
+        .into_iter()
+        .map(|added| {
+            let mut tally: BTreeMap<(String, Option<String>), usize> = BTreeMap::new();
+            for id in &added {
+                if let Some(inst) = world.instance(*id) {
+                    *tally.entry((inst.part.clone(), inst.color.clone())).or_default() += 1;
+                }
+            }
+            let parts = tally
+                .into_iter()
+                .map(|((part, color), count)| PartCount { part, color, count })
+                .collect();
+            InstructionStep { added, parts }
+        })
+        .collect()
+}
+
+/// Camera for step `index`, in normalized world units: framing everything
+/// built so far from above, on the side the new parts lie towards.
+pub fn step_camera(world: &World, steps: &[InstructionStep], index: usize) -> (Vec3, Vec3) {
+    let scale = lego::SCALE_NORMALIZED;
+    let centre_of = |ids: &mut dyn Iterator<Item = &PartInstanceId>| {
+        let (mut lo, mut hi) = (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN));
+        for id in ids {
+            for c in world.instance_cells(*id).unwrap_or(&[]) {
+                let a = Vec3::from(scale.cell_to_world(c.x as f32, c.y as f32, c.z as f32));
+                let b = Vec3::from(scale.cell_to_world(
+                    c.x as f32 + 1.0,
+                    c.y as f32 + 1.0,
+                    c.z as f32 + 1.0,
+                ));
+                lo = lo.min(a);
+                hi = hi.max(b);
+            }
+        }
+        if lo.x > hi.x {
+            (Vec3::ZERO, Vec3::ZERO)
+        } else {
+            ((lo + hi) * 0.5, hi - lo)
+        }
+    };
+
+    let (target, extent) = centre_of(&mut steps[..=index].iter().flat_map(|s| &s.added));
+    let (new_centre, _) = centre_of(&mut steps[index].added.iter());
+
+    let side = |d: f32, fallback: f32| if d.abs() < 1e-3 { fallback } else { d.signum() };
+    let towards = new_centre - target;
+    let dir = Vec3::new(side(towards.x, 1.0), side(towards.y, -1.0), 1.2).normalize();
+    let distance = extent.length().max(2.0) * 1.6;
+
+    (target + dir * distance, target)
+}
+
+/// Flat RGB for a color name or `#rrggbb`; unknown names come out grey.
+pub fn color_rgb(color: Option<&str>) -> [f32; 3] {
+    let Some(name) = color else {
+        return [0.75, 0.75, 0.75];
+    };
+    if let Some(hex) = name.strip_prefix('#') {
+        if hex.len() == 6 {
+            if let Ok(v) = u32::from_str_radix(hex, 16) {
+                return [(v >> 16) & 0xff, (v >> 8) & 0xff, v & 0xff].map(|c| c as f32 / 255.0);
+            }
+        }
+    }
+    match name.to_ascii_lowercase().as_str() {
+        "red" => [0.70, 0.05, 0.05],
+        "blue" => [0.05, 0.25, 0.65],
+        "yellow" => [0.95, 0.80, 0.05],
+        "green" => [0.10, 0.50, 0.15],
+        "white" => [0.95, 0.95, 0.95],
+        "black" => [0.08, 0.08, 0.08],
+        "orange" => [0.95, 0.45, 0.05],
+        "tan" => [0.85, 0.75, 0.55],
+        "brown" => [0.35, 0.20, 0.10],
+        _ => [0.75, 0.75, 0.75],
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// File name of the image for step `index` (zero based).
+pub fn step_image_name(index: usize) -> String {
+    format!("step-{:03}.png", index + 1)
+}
+
+/// The booklet page: one section per step with its part list, plus its
+/// image when `with_images` is set.
+pub fn booklet_html(title: &str, steps: &[InstructionStep], with_images: bool) -> String {
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str(&format!("<title>{}</title>\n", escape(title)));
+    html.push_str(
+        "<style>\nbody { font-family: sans-serif; margin: 2em; }\n\
+         section { page-break-after: always; margin-bottom: 3em; }\n\
+         img { max-width: 100%; border: 1px solid #ccc; }\n</style>\n",
+    );
+    html.push_str("</head>\n<body>\n");
+    html.push_str(&format!("<h1>{}</h1>\n", escape(title)));
+
+    for (i, step) in steps.iter().enumerate() {
+        html.push_str(&format!("<section id=\"step-{}\">\n", i + 1));
+        html.push_str(&format!("<h2>Step {} of {}</h2>\n", i + 1, steps.len()));
+        html.push_str("<ul class=\"parts\">\n");
+        for p in &step.parts {
+            let color = p.color.as_deref().map(|c| format!(" ({})", escape(c))).unwrap_or_default();
+            html.push_str(&format!("<li>{}&times; {}{}</li>\n", p.count, escape(&p.part), color));
+        }
+        html.push_str("</ul>\n");
+        if with_images {
+            html.push_str(&format!(
+                "<img src=\"{}\" alt=\"Step {}\">\n",
+                step_image_name(i),
+                i + 1
+            ));
+        }
+        html.push_str("</section>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// What the picture of step `index` shows: every part added so far, in
+/// build order. New parts keep their color (orange when they have none);
+/// earlier ones fade towards white.
+pub fn step_solids<'w>(
+    world: &'w World,
+    steps: &[InstructionStep],
+    index: usize,
+) -> Vec<Solid<'w>> {
+    steps[..=index]
+        .iter()
+        .enumerate()
+        .flat_map(|(s, step)| step.added.iter().map(move |id| (s == index, *id)))
+        .filter_map(|(new, id)| {
+            let inst = world.instance(id)?;
+            let cells = world.instance_cells(id)?;
+            let base = color_rgb(inst.color.as_deref());
+            let color = match (new, inst.color.is_some()) {
+                (true, true) => base,
+                (true, false) => color_rgb(Some("orange")),
+                (false, _) => base.map(|c| c * 0.35 + 0.6),
+            };
+            Some(Solid { cells, color })
+        })
+        .collect()
+}
+
+/// Plans an assembly order, groups it into steps and writes `index.html`
+/// (plus `step-NNN.png` images) into `dir`. Fails when the model has no
+/// feasible build order.
+pub fn export_instructions<P: AsRef<Path>>(
+    world: &World,
+    title: &str,
+    dir: P,
+    options: &InstructionOptions,
+) -> Result<Vec<InstructionStep>, String> {
+    let dir = dir.as_ref();
+    let plan = plan_assembly(world);
+    if !plan.is_feasible() {
+        let stuck: Vec<_> = plan.stuck.iter().map(|id| id.0.to_string()).collect();
+        return Err(format!("no feasible build order; stuck instances: {}", stuck.join(", ")));
+    }
+
+    let steps = group_steps(world, &plan, options.max_parts_per_step);
+    fs::create_dir_all(dir).map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+
+    if options.render_images {
+        let (w, h) = options.image_size;
+        let renderer = pollster::block_on(OffscreenRenderer::new(w, h))
+            .map_err(|e| format!("failed to start renderer: {:#}", e))?;
+
+        for index in 0..steps.len() {
+            let solids = step_solids(world, &steps, index);
+            let (eye, target) = step_camera(world, &steps, index);
+            let path = dir.join(step_image_name(index));
+            renderer
+                .render_png(&solids, eye, target, &path)
+                .map_err(|e| format!("failed to render step {}: {:#}", index + 1, e))?;
+        }
+    }
+
+    let html = booklet_html(title, &steps, options.render_images);
+    let index = dir.join("index.html");
+    fs::write(&index, html).map_err(|e| format!("failed to write {}: {}", index.display(), e))?;
+
+    Ok(steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader;
+    use crate::world::WorldCell;
+
+    fn tower() -> World {
+        let brick = loader::load_part_dir("assets/parts/lego/3001").unwrap();
+        let block = loader::load_part_dir("assets/parts/lego/3003").unwrap();
+        let mut world = World::new();
+        for y in [0, 4, 8] {
+            world.place_part(&brick, WorldCell { x: 0, y, z: 0 }).unwrap();
+        }
+        let cap = world.place_part(&block, WorldCell { x: 0, y: 3, z: 3 }).unwrap();
+        world.set_color(cap, Some("red".to_string())).unwrap();
+        world
+    }
+
+    #[test]
+    fn steps_respect_size_and_layers() {
+        let world = tower();
+        let plan = plan_assembly(&world);
+
+        let steps = group_steps(&world, &plan, 2);
+        let sizes: Vec<_> = steps.iter().map(|s| s.added.len()).collect();
+        assert_eq!(sizes, vec![2, 1, 1]);
+        assert_eq!(
+            steps[0].parts,
+            vec![PartCount { part: "lego:3001".to_string(), color: None, count: 2 }]
+        );
+        assert_eq!(steps[2].parts[0].color.as_deref(), Some("red"));
+
+        assert_eq!(group_steps(&world, &plan, 10).len(), 2);
+    }
+
+    #[test]
+    fn camera_looks_at_the_model_from_above() {
+        let world = tower();
+        let steps = group_steps(&world, &plan_assembly(&world), 4);
+
+        let (eye, target) = step_camera(&world, &steps, 1);
+        assert!(eye.z > target.z);
+        assert!((target.y - 6.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn writes_a_text_booklet() {
+        let world = tower();
+        let dir = std::env::temp_dir().join(format!("ssm-booklet-{}", std::process::id()));
+        let options = InstructionOptions { render_images: false, ..Default::default() };
+
+        let steps = export_instructions(&world, "Tower <1>", &dir, &options).unwrap();
+        let html = fs::read_to_string(dir.join("index.html")).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(steps.len(), 2);
+        assert!(html.contains("<title>Tower &lt;1&gt;</title>"));
+        assert!(html.contains("Step 2 of 2"));
+        assert!(html.contains("3&times; lego:3001"));
+        assert!(html.contains("1&times; lego:3003 (red)"));
+        assert!(!html.contains("<img"));
+
+        assert!(booklet_html("t", &steps, true).contains("src=\"step-002.png\""));
+    }
+
+    #[test]
+    fn highlights_the_parts_each_step_adds() {
+        let world = tower();
+        let steps = group_steps(&world, &plan_assembly(&world), 4);
+
+        let first = step_solids(&world, &steps, 0);
+        assert_eq!(first.len(), 3);
+        assert!(first.iter().all(|s| s.color == color_rgb(Some("orange"))));
+
+        let second = step_solids(&world, &steps, 1);
+        assert_eq!(second.len(), 4);
+        assert_eq!(second[3].color, color_rgb(Some("red")));
+        assert!(second[..3].iter().all(|s| s.color.iter().all(|&c| c >= 0.6)));
+    }
+
+    #[test]
+    fn renders_step_images() {
+        let world = tower();
+        let dir = std::env::temp_dir().join(format!("ssm-images-{}", std::process::id()));
+        let options = InstructionOptions { image_size: (64, 48), ..Default::default() };
+
+        let result = export_instructions(&world, "Tower", &dir, &options);
+        if let Err(e) = &result {
+            if e.starts_with("failed to start renderer") {
+                eprintln!("skipping: {}", e);
+                let _ = fs::remove_dir_all(&dir);
+                return;
+            }
+        }
+        let steps = result.unwrap();
+
+        for index in 0..steps.len() {
+            let file = fs::File::open(dir.join(step_image_name(index))).unwrap();
+            let reader = png::Decoder::new(file).read_info().unwrap();
+            assert_eq!((reader.info().width, reader.info().height), (64, 48));
+        }
+        assert!(fs::read_to_string(dir.join("index.html")).unwrap().contains("<img"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn parses_colors() {
+        assert_eq!(color_rgb(Some("#ff0080")), [1.0, 0.0, 128.0 / 255.0]);
+        assert_eq!(color_rgb(Some("Red")), color_rgb(Some("red")));
+        assert_eq!(color_rgb(None), color_rgb(Some("no such color")));
+    }
+}
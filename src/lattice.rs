@@ -1,4 +1,4 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize)]
 pub struct Lattice {
@@ -14,7 +14,7 @@ pub struct Units {
     pub z: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 pub struct Bounds {
     pub x: [i32; 2],
     pub y: [i32; 2],
@@ -1,8 +1,28 @@
 pub mod part;
+pub mod assembly;
+pub mod bom;
 pub mod lattice;
 pub mod connectors;
 pub mod loader;
+pub mod catalog;
+pub mod diff;
+pub mod chunk;
 pub mod world;
+pub mod graph;
+pub mod history;
+pub mod instructions;
+pub mod model;
+pub mod orientation;
+pub mod pattern;
+pub mod placement;
+pub mod query;
+pub mod raycast;
+pub mod snap;
+pub mod stability;
+pub mod submodel;
+pub mod support;
+pub mod rational;
+pub mod units;
 pub mod visuals;
 
 #[cfg(test)]
@@ -12,10 +32,10 @@ mod tests {
     #[test]
     fn loads_known_parts() {
         let cases = [
-            ("assets/parts/lego/3001", "lego:3001", 24usize, 8usize),
-            ("assets/parts/lego/3002", "lego:3002", 18usize, 6usize),
-            ("assets/parts/lego/3003", "lego:3003", 12usize, 4usize),
-            ("assets/parts/lego/3004", "lego:3004", 6usize,  2usize),
+            ("assets/parts/lego/3001", "lego:3001", 24usize, 16usize),
+            ("assets/parts/lego/3002", "lego:3002", 18usize, 12usize),
+            ("assets/parts/lego/3003", "lego:3003", 12usize, 8usize),
+            ("assets/parts/lego/3004", "lego:3004", 6usize,  4usize),
         ];
 
         for (path, id, occ, conns) in cases {
@@ -60,4 +80,689 @@ mod tests {
 
         assert!(err.contains("occupied"));
     }
+
+    #[test]
+    fn stacked_parts_are_connected() {
+        let brick = loader::load_part_dir("assets/parts/lego/3001").unwrap();
+        let small = loader::load_part_dir("assets/parts/lego/3004").unwrap();
+        let mut world = crate::world::World::new();
+
+        let base = world
+            .place_part(&brick, crate::world::WorldCell { x: 0, y: 0, z: 0 })
+            .unwrap();
+        let top = world
+            .place_part(&small, crate::world::WorldCell { x: 1, y: 2, z: 3 })
+            .unwrap();
+
+        let graph = world.connections();
+        assert_eq!(graph.neighbors(base), vec![top]);
+        assert_eq!(graph.mates(top, base).len(), 2);
+        assert!(graph.is_one_piece());
+    }
+
+    #[test]
+    fn adjacent_parts_are_not_connected() {
+        let part = loader::load_part_dir("assets/parts/lego/3003").unwrap();
+        let mut world = crate::world::World::new();
+
+        let a = world
+            .place_part(&part, crate::world::WorldCell { x: 0, y: 0, z: 0 })
+            .unwrap();
+        let b = world
+            .place_part(&part, crate::world::WorldCell { x: 2, y: 0, z: 0 })
+            .unwrap();
+
+        let graph = world.connections();
+        assert!(graph.neighbors(a).is_empty());
+        assert_eq!(graph.connected_components(), vec![vec![a], vec![b]]);
+        assert!(!graph.is_one_piece());
+    }
+
+    #[test]
+    fn extended_connector_kinds_carry_freedom() {
+        use crate::connectors::{ConnectorFile, ConnectorType, Freedom};
+
+        let file: ConnectorFile = serde_json::from_str(
+            r#"{ "connectors": [
+                { "type": "axle", "pos": { "x": 0, "y": 0, "z": 1 }, "dir": "+x" },
+                { "type": "pin_hole", "pos": { "x": 1, "y": 0, "z": 1 }, "dir": "-x" },
+                { "type": "ball", "pos": { "x": 0, "y": 0, "z": 2 }, "dir": "+z" }
+            ] }"#,
+        )
+        .unwrap();
+
+        let kinds: Vec<ConnectorType> = file.connectors.iter().map(|c| c.kind).collect();
+        assert_eq!(kinds, [ConnectorType::Axle, ConnectorType::PinHole, ConnectorType::Ball]);
+
+        assert_eq!(
+            ConnectorType::Axle.joint_freedom(ConnectorType::PinHole),
+            Some(Freedom::Rotational)
+        );
+        assert_eq!(
+            ConnectorType::Axle.joint_freedom(ConnectorType::AxleHole),
+            Some(Freedom::Fixed)
+        );
+        assert_eq!(
+            ConnectorType::Socket.joint_freedom(ConnectorType::Ball),
+            Some(Freedom::Spherical)
+        );
+        assert_eq!(ConnectorType::Stud.joint_freedom(ConnectorType::Clip), None);
+    }
+
+    #[test]
+    fn fractional_connector_positions() {
+        use crate::connectors::{ConnectorFile, Position};
+        use crate::rational::Rational;
+        use crate::units::lego;
+
+        let file: ConnectorFile = serde_json::from_str(
+            r#"{ "connectors": [
+                { "type": "stud", "pos": { "x": 0, "y": "1/2", "z": 1 }, "dir": "+z" },
+                { "type": "pin_hole", "pos": { "x": "1/2", "y": 0, "z": "5/3" }, "dir": "+y" }
+            ] }"#,
+        )
+        .unwrap();
+
+        let jumper = file.connectors[0].pos;
+        assert_eq!(jumper, Position::new(0, Rational::new(2, 4), 1));
+
+        let mm = file.connectors[1].pos.to_world(lego::SCALE_MM);
+        assert!((mm[0] - lego::STUD_MM / 2.0).abs() < 1e-4);
+        assert!((mm[2] - 5.0 * lego::PLATE_MM / 3.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn connector_facing_forms() {
+        use crate::connectors::{ConnectorFile, ConnectorType, Position};
+        use crate::world::WorldConnector;
+        use glam::Vec3;
+
+        let file: ConnectorFile = serde_json::from_str(
+            r#"{ "connectors": [
+                { "type": "hinge", "pos": { "x": 0, "y": 0, "z": 1 }, "dir": "+y" },
+                { "type": "hinge", "pos": { "x": 0, "y": 0, "z": 1 },
+                  "dir": { "normal": [0.0, -1.0, 0.0001], "up": [0, 0, 1] } },
+                { "type": "stud", "pos": { "x": 0, "y": 0, "z": 3 },
+                  "dir": { "quat": [0.0, 0.38268343, 0.0, 0.9238795] } }
+            ] }"#,
+        )
+        .unwrap();
+        file.validate().unwrap();
+
+        let normals: Vec<Vec3> = file.connectors.iter().map(|c| c.dir.normal()).collect();
+        assert!(normals[0].abs_diff_eq(Vec3::Y, 1e-6));
+        assert!(normals[2].abs_diff_eq(Vec3::new(0.70710677, 0.0, 0.70710677), 1e-5));
+
+        let placed = |i: usize| WorldConnector {
+            index: i,
+            kind: file.connectors[i].kind,
+            pos: Position::new(0, 0, 1),
+            normal: normals[i],
+        };
+        assert!(placed(0).mates(&placed(1)));
+        assert!(!placed(0).mates(&WorldConnector { normal: Vec3::NEG_Z, ..placed(1) }));
+        assert_eq!(placed(1).kind, ConnectorType::Hinge);
+
+        let parallel: ConnectorFile = serde_json::from_str(
+            r#"{ "connectors": [
+                { "type": "clip", "pos": { "x": 0, "y": 0, "z": 0 },
+                  "dir": { "normal": [1, 0, 0], "up": [2, 0, 0] } }
+            ] }"#,
+        )
+        .unwrap();
+        assert!(parallel.validate().is_err());
+    }
+
+    fn without_tubes(path: &str) -> loader::Part {
+        let mut part = loader::load_part_dir(path).unwrap();
+        part.connectors
+            .connectors
+            .retain(|c| c.kind != crate::connectors::ConnectorType::Tube);
+        part
+    }
+
+    #[test]
+    fn studs_block_parts_without_tubes() {
+        let base = loader::load_part_dir("assets/parts/lego/3001").unwrap();
+        let block = without_tubes("assets/parts/lego/3004");
+        let mut world = crate::world::World::new();
+
+        world
+            .place_part(&base, crate::world::WorldCell { x: 0, y: 0, z: 0 })
+            .unwrap();
+
+        let err = world
+            .place_part(&block, crate::world::WorldCell { x: 0, y: 0, z: 3 })
+            .unwrap_err();
+        assert!(err.contains("protruding"));
+
+        // Hanging entirely off the base, nothing is in the way.
+        world
+            .place_part(&block, crate::world::WorldCell { x: 2, y: 0, z: 3 })
+            .unwrap();
+
+        world.set_protrusion_checks(false);
+        world
+            .place_part(&block, crate::world::WorldCell { x: 0, y: 0, z: 3 })
+            .unwrap();
+    }
+
+    #[test]
+    fn studs_cannot_push_into_parts_above() {
+        let base = loader::load_part_dir("assets/parts/lego/3001").unwrap();
+        let block = without_tubes("assets/parts/lego/3004");
+        let mut world = crate::world::World::new();
+
+        world
+            .place_part(&block, crate::world::WorldCell { x: 1, y: 1, z: 3 })
+            .unwrap();
+
+        let err = world
+            .place_part(&base, crate::world::WorldCell { x: 0, y: 0, z: 0 })
+            .unwrap_err();
+        assert!(err.contains("protruding"));
+
+        // A real brick takes the studs on its tubes.
+        let brick = loader::load_part_dir("assets/parts/lego/3004").unwrap();
+        let mut world = crate::world::World::new();
+        world
+            .place_part(&brick, crate::world::WorldCell { x: 1, y: 1, z: 3 })
+            .unwrap();
+        world
+            .place_part(&base, crate::world::WorldCell { x: 0, y: 0, z: 0 })
+            .unwrap();
+    }
+
+    #[test]
+    fn remove_part_frees_cells_and_connections() {
+        let base = loader::load_part_dir("assets/parts/lego/3001").unwrap();
+        let brick = loader::load_part_dir("assets/parts/lego/3004").unwrap();
+        let mut world = crate::world::World::new();
+
+        let a = world
+            .place_part(&base, crate::world::WorldCell { x: 0, y: 0, z: 0 })
+            .unwrap();
+        let b = world
+            .place_part(&brick, crate::world::WorldCell { x: 0, y: 0, z: 3 })
+            .unwrap();
+
+        world.remove_part(a).unwrap();
+
+        assert_eq!(world.occupancy_len(), 6);
+        assert!(world.instance_cells(a).is_none());
+        assert!(world.connections().neighbors(b).is_empty());
+        assert!(world.remove_part(a).is_err());
+
+        world
+            .place_part(&base, crate::world::WorldCell { x: 0, y: 0, z: 0 })
+            .unwrap();
+        assert_eq!(world.connections().neighbors(b).len(), 1);
+    }
+
+    #[test]
+    fn move_part_reindexes_instance() {
+        let base = loader::load_part_dir("assets/parts/lego/3001").unwrap();
+        let brick = loader::load_part_dir("assets/parts/lego/3004").unwrap();
+        let mut world = crate::world::World::new();
+
+        let a = world
+            .place_part(&base, crate::world::WorldCell { x: 0, y: 0, z: 0 })
+            .unwrap();
+        let b = world
+            .place_part(&brick, crate::world::WorldCell { x: 5, y: 0, z: 0 })
+            .unwrap();
+        assert!(world.connections().neighbors(a).is_empty());
+
+        world
+            .move_part(b, crate::world::WorldCell { x: 1, y: 1, z: 3 })
+            .unwrap();
+
+        assert_eq!(world.instance_at(crate::world::WorldCell { x: 1, y: 2, z: 4 }), Some(b));
+        assert!(!world.is_occupied(crate::world::WorldCell { x: 5, y: 0, z: 0 }));
+        assert_eq!(world.connections().neighbors(a), vec![b]);
+
+        // Moving into the base fails and leaves the brick on top of it.
+        let err = world
+            .move_part(b, crate::world::WorldCell { x: 0, y: 0, z: 0 })
+            .unwrap_err();
+        assert!(err.contains("occupied"));
+        assert_eq!(world.instance_at(crate::world::WorldCell { x: 1, y: 1, z: 3 }), Some(b));
+        assert_eq!(world.connections().neighbors(a), vec![b]);
+        assert_eq!(world.occupancy_len(), 30);
+
+        // So does a move whose cells would fall off the lattice.
+        world.move_part(b, crate::world::WorldCell { x: -5, y: 0, z: 0 }).unwrap();
+        for x in [i32::MAX, i32::MAX - 1] {
+            let err = world
+                .move_part(b, crate::world::WorldCell { x, y: 0, z: 0 })
+                .unwrap_err();
+            assert!(err.contains("out of range"));
+        }
+        assert_eq!(world.instance(b).unwrap().offset.x, -5);
+    }
+
+    #[test]
+    fn instance_records_follow_edits() {
+        use crate::orientation::Orientation;
+
+        let base = loader::load_part_dir("assets/parts/lego/3001").unwrap();
+        let brick = loader::load_part_dir("assets/parts/lego/3004").unwrap();
+        let mut world = crate::world::World::new();
+
+        let a = world
+            .place_part(&base, crate::world::WorldCell { x: 0, y: 0, z: 0 })
+            .unwrap();
+        let b = world
+            .place_part_oriented(&brick, crate::world::WorldCell { x: 0, y: 0, z: 3 }, Orientation::Deg90)
+            .unwrap();
+
+        world.set_color(b, Some("red".to_string())).unwrap();
+        world.set_label(b, Some("lintel".to_string())).unwrap();
+        world.move_part(b, crate::world::WorldCell { x: 0, y: 2, z: 3 }).unwrap();
+
+        let rec = world.instance(b).unwrap();
+        assert_eq!(rec.part, "lego:3004");
+        assert_eq!(rec.system, "lego");
+        assert_eq!(rec.offset, crate::world::WorldCell { x: 0, y: 2, z: 3 });
+        assert_eq!(rec.orientation, Orientation::Deg90);
+        assert_eq!(rec.color.as_deref(), Some("red"));
+        assert_eq!(rec.label.as_deref(), Some("lintel"));
+
+        let ids: Vec<_> = world.instances().map(|(id, _)| id).collect();
+        assert_eq!(ids, vec![a, b]);
+
+        world.remove_part(a).unwrap();
+        assert_eq!(world.instance_count(), 1);
+        assert!(world.instance(a).is_none());
+        assert!(world.set_color(a, None).is_err());
+    }
+
+    #[test]
+    fn undo_and_redo_world_edits() {
+        let base = loader::load_part_dir("assets/parts/lego/3001").unwrap();
+        let brick = loader::load_part_dir("assets/parts/lego/3004").unwrap();
+        let mut world = crate::world::World::new();
+
+        let a = world
+            .place_part(&base, crate::world::WorldCell { x: 0, y: 0, z: 0 })
+            .unwrap();
+        let b = world
+            .place_part(&brick, crate::world::WorldCell { x: 0, y: 0, z: 3 })
+            .unwrap();
+        world.move_part(b, crate::world::WorldCell { x: 1, y: 2, z: 3 }).unwrap();
+        world.set_color(b, Some("blue".to_string())).unwrap();
+        world.remove_part(a).unwrap();
+
+        assert!(world.undo().unwrap());
+        assert_eq!(world.connections().neighbors(a), vec![b]);
+
+        assert!(world.undo().unwrap());
+        assert_eq!(world.instance(b).unwrap().color, None);
+
+        assert!(world.undo().unwrap());
+        assert_eq!(world.instance(b).unwrap().offset, crate::world::WorldCell { x: 0, y: 0, z: 3 });
+
+        assert!(world.redo().unwrap());
+        assert_eq!(world.instance(b).unwrap().offset, crate::world::WorldCell { x: 1, y: 2, z: 3 });
+
+        assert!(world.undo().unwrap());
+        assert!(world.undo().unwrap());
+        assert!(world.undo().unwrap());
+        assert!(!world.undo().unwrap());
+        assert_eq!(world.occupancy_len(), 0);
+
+        assert!(world.redo().unwrap());
+        assert!(world.redo().unwrap());
+        assert_eq!(world.instance_count(), 2);
+        assert_eq!(world.connections().neighbors(a), vec![b]);
+    }
+
+    #[test]
+    fn labels_undo_like_other_edits() {
+        use crate::world::{WorldCell, WorldEvent};
+
+        let brick = loader::load_part_dir("assets/parts/lego/3004").unwrap();
+        let mut world = crate::world::World::new();
+        let events = world.subscribe();
+        let a = world.place_part(&brick, WorldCell { x: 0, y: 0, z: 0 }).unwrap();
+        world.set_label(a, Some("door".to_string())).unwrap();
+
+        assert!(world.undo().unwrap());
+        assert_eq!(world.instance(a).unwrap().label, None);
+        assert!(world.undo().unwrap());
+        assert!(world.redo().unwrap());
+        assert!(world.redo().unwrap());
+        assert_eq!(world.instance(a).unwrap().label.as_deref(), Some("door"));
+
+        let err = world.transaction(|world| {
+            world.set_label(a, None)?;
+            Err::<(), _>("no".to_string())
+        });
+        assert!(err.is_err());
+        assert_eq!(world.instance(a).unwrap().label.as_deref(), Some("door"));
+
+        // Setting what is already there is not an edit.
+        world.set_label(a, Some("door".to_string())).unwrap();
+        world.set_color(a, None).unwrap();
+        assert_eq!(world.history().undo_len(), 2);
+
+        let relabels = events
+            .try_iter()
+            .filter(|e| matches!(e, WorldEvent::Relabeled { .. }))
+            .count();
+        assert_eq!(relabels, 3);
+    }
+
+    #[test]
+    fn undo_ignores_rules_changed_since_the_edit() {
+        use crate::world::{Ground, WorldCell};
+
+        let brick = loader::load_part_dir("assets/parts/lego/3004").unwrap();
+        let mut world = crate::world::World::new();
+        let a = world.place_part(&brick, WorldCell { x: 0, y: 0, z: 0 }).unwrap();
+        let b = world.place_part(&brick, WorldCell { x: 2, y: 0, z: 0 }).unwrap();
+        world.move_part(b, WorldCell { x: 4, y: 0, z: 0 }).unwrap();
+        world.remove_part(a).unwrap();
+        world.remove_part(b).unwrap();
+
+        // Both bricks now sit below the ground, yet their history replays.
+        world.set_ground(Some(Ground::Plane { z: 1 }));
+        assert!(world.undo().unwrap());
+        assert!(world.undo().unwrap());
+        assert_eq!(world.instance_count(), 2);
+        assert!(world.undo().unwrap());
+        assert_eq!(world.instance(b).unwrap().offset, WorldCell { x: 2, y: 0, z: 0 });
+
+        assert!(world.redo().unwrap());
+        assert_eq!(world.instance(b).unwrap().offset, WorldCell { x: 4, y: 0, z: 0 });
+        assert!(world.history().can_redo());
+        assert!(world.place_part(&brick, WorldCell { x: 8, y: 0, z: 0 }).is_err());
+    }
+
+    #[test]
+    fn grouped_edits_undo_together() {
+        let brick = loader::load_part_dir("assets/parts/lego/3004").unwrap();
+        let mut world = crate::world::World::new();
+        world.set_history_limit(2);
+
+        world.begin_group();
+        for x in 0..4 {
+            world
+                .place_part(&brick, crate::world::WorldCell { x, y: 0, z: 0 })
+                .unwrap();
+        }
+        world.end_group();
+        assert_eq!(world.history().undo_len(), 1);
+
+        let lone = world
+            .place_part(&brick, crate::world::WorldCell { x: 9, y: 0, z: 0 })
+            .unwrap();
+        world.remove_part(lone).unwrap();
+        assert_eq!(world.history().undo_len(), 2);
+
+        // The grouped placement fell off the end of the history.
+        assert!(world.undo().unwrap());
+        assert!(world.undo().unwrap());
+        assert!(!world.undo().unwrap());
+        assert_eq!(world.instance_count(), 4);
+
+        world.set_history_limit(10);
+        world.begin_group();
+        world.remove_part(crate::world::PartInstanceId(1)).unwrap();
+        world.remove_part(crate::world::PartInstanceId(2)).unwrap();
+        world.end_group();
+        assert!(world.undo().unwrap());
+        assert_eq!(world.instance_count(), 4);
+
+        // A new edit discards what could have been redone.
+        world.remove_part(crate::world::PartInstanceId(3)).unwrap();
+        assert!(!world.redo().unwrap());
+    }
+
+    #[test]
+    fn failed_placement_does_not_consume_an_id() {
+        let part = loader::load_part_dir("assets/parts/lego/3003").unwrap();
+        let mut world = crate::world::World::new();
+
+        world
+            .place_part(&part, crate::world::WorldCell { x: 0, y: 0, z: 0 })
+            .unwrap();
+        world
+            .place_part(&part, crate::world::WorldCell { x: 1, y: 0, z: 0 })
+            .unwrap_err();
+
+        let id = world
+            .place_part(&part, crate::world::WorldCell { x: 2, y: 0, z: 0 })
+            .unwrap();
+        assert_eq!(id.0, 2);
+    }
+
+    #[test]
+    fn batch_placement_is_all_or_nothing() {
+        use crate::orientation::Orientation;
+        use crate::world::{Placement, WorldCell};
+
+        let base = loader::load_part_dir("assets/parts/lego/3001").unwrap();
+        let brick = loader::load_part_dir("assets/parts/lego/3004").unwrap();
+        let mut world = crate::world::World::new();
+        world.place_part(&base, WorldCell { x: 0, y: 0, z: 0 }).unwrap();
+
+        let at = |part, x, y, z| Placement {
+            part,
+            offset: WorldCell { x, y, z },
+            orientation: Orientation::Deg0,
+        };
+
+        // The last two bricks overlap each other, not the world.
+        let err = world
+            .place_batch(&[
+                at(&brick, 0, 0, 3),
+                at(&brick, 1, 0, 3),
+                at(&brick, 5, 0, 0),
+                at(&brick, 5, 1, 0),
+            ])
+            .unwrap_err();
+        assert!(err.starts_with("placement 3:"));
+        assert_eq!(world.instance_count(), 1);
+        assert_eq!(world.occupancy_len(), 24);
+        assert!(world.connections().neighbors(crate::world::PartInstanceId(1)).is_empty());
+
+        let ids = world
+            .place_batch(&[at(&brick, 0, 0, 3), at(&brick, 1, 0, 3)])
+            .unwrap();
+        assert_eq!(ids.iter().map(|id| id.0).collect::<Vec<_>>(), vec![2, 3]);
+
+        assert!(world.undo().unwrap());
+        assert_eq!(world.instance_count(), 1);
+    }
+
+    #[test]
+    fn subscribers_hear_about_settled_changes() {
+        use crate::orientation::Orientation;
+        use crate::world::{Placement, WorldCell, WorldEvent};
+
+        let base = loader::load_part_dir("assets/parts/lego/3001").unwrap();
+        let brick = loader::load_part_dir("assets/parts/lego/3004").unwrap();
+        let mut world = crate::world::World::new();
+        let events = world.subscribe();
+        let drain = || events.try_iter().collect::<Vec<_>>();
+
+        let a = world.place_part(&base, WorldCell { x: 0, y: 0, z: 0 }).unwrap();
+        world.set_color(a, Some("red".to_string())).unwrap();
+        world.move_part(a, WorldCell { x: 1, y: 0, z: 0 }).unwrap();
+        let seen = drain();
+        assert_eq!(seen.len(), 3);
+        assert!(matches!(&seen[0], WorldEvent::Added { id, record } if *id == a
+            && record.footprint.cells.len() == 24));
+        assert!(matches!(&seen[1], WorldEvent::Recolored { to: Some(c), .. } if c == "red"));
+        assert!(matches!(seen[2], WorldEvent::Moved { to: WorldCell { x: 1, .. }, .. }));
+
+        // A failed batch never happened as far as observers can tell.
+        let at = |x| Placement {
+            part: &brick,
+            offset: WorldCell { x, y: 0, z: 3 },
+            orientation: Orientation::Deg0,
+        };
+        world.place_batch(&[at(1), at(1)]).unwrap_err();
+        assert!(drain().is_empty());
+
+        world.place_batch(&[at(1), at(2)]).unwrap();
+        assert_eq!(drain().len(), 2);
+
+        world.undo().unwrap();
+        let seen = drain();
+        assert_eq!(seen.len(), 2);
+        assert!(seen.iter().all(|e| matches!(e, WorldEvent::Removed { .. })));
+
+        // Dropped receivers are forgotten.
+        drop(events);
+        world.remove_part(a).unwrap();
+    }
+
+    #[test]
+    fn failed_transactions_leave_history_and_rules_alone() {
+        use crate::lattice::Bounds;
+        use crate::orientation::Orientation;
+        use crate::world::{Placement, WorldCell};
+
+        let brick = loader::load_part_dir("assets/parts/lego/3004").unwrap();
+        let mut world = crate::world::World::new();
+        let a = world.place_part(&brick, WorldCell { x: 0, y: 0, z: 0 }).unwrap();
+        world.place_part(&brick, WorldCell { x: 4, y: 0, z: 0 }).unwrap();
+        assert!(world.undo().unwrap());
+
+        let at = |x| Placement {
+            part: &brick,
+            offset: WorldCell { x, y: 0, z: 0 },
+            orientation: Orientation::Deg0,
+        };
+        assert!(world.place_batch(&[at(2), at(0)]).is_err());
+        assert!(world.history().can_redo());
+
+        // Rolling back must not trip over rules changed along the way.
+        let err = world
+            .transaction(|world| {
+                world.remove_part(a)?;
+                world.set_bounds(Some(Bounds { x: [5, 9], y: [0, 9], z: [0, 9] }));
+                Err::<(), _>("changed my mind".to_string())
+            })
+            .unwrap_err();
+        assert_eq!(err, "changed my mind");
+        assert_eq!(world.instance_count(), 1);
+        assert_eq!(world.bounds(), None);
+
+        let err = world
+            .transaction(|world| {
+                world.remove_part(a)?;
+                world.clear_history()?;
+                Ok(())
+            })
+            .unwrap_err();
+        assert!(err.contains("cannot clear history"));
+        assert_eq!(world.instance_count(), 1);
+
+        assert!(world.redo().unwrap());
+        assert_eq!(world.instance_count(), 2);
+    }
+
+    #[test]
+    fn dry_run_reports_every_conflict() {
+        use crate::lattice::Bounds;
+        use crate::orientation::Orientation;
+        use crate::placement::ProtrusionConflict;
+        use crate::world::WorldCell;
+
+        let base = loader::load_part_dir("assets/parts/lego/3001").unwrap();
+        let brick = loader::load_part_dir("assets/parts/lego/3004").unwrap();
+        let block = without_tubes("assets/parts/lego/3003");
+        let mut world = crate::world::World::new();
+        world.set_bounds(Some(Bounds { x: [0, 9], y: [0, 9], z: [0, 20] }));
+
+        let a = world.place_part(&base, WorldCell { x: 0, y: 0, z: 0 }).unwrap();
+        let b = world.place_part(&brick, WorldCell { x: 0, y: 0, z: 3 }).unwrap();
+
+        // A 2x2 without tubes: one row hangs off the base and out of the
+        // world, the other covers the 1x2 column and a bare stud.
+        let report = world.check_placement(&block, WorldCell { x: 0, y: -1, z: 3 }, Orientation::Deg0);
+
+        assert_eq!(
+            report.collisions,
+            (3..6).map(|z| (WorldCell { x: 0, y: 0, z }, b)).collect::<Vec<_>>()
+        );
+        assert_eq!(report.out_of_bounds.len(), 6);
+        assert!(report.out_of_bounds.iter().all(|c| c.y == -1));
+        assert_eq!(
+            report.unmatched_protrusions,
+            vec![
+                ProtrusionConflict::Blocked {
+                    cell: WorldCell { x: 0, y: 0, z: 3 },
+                    instance: a,
+                    connector: 0,
+                },
+                ProtrusionConflict::Blocked {
+                    cell: WorldCell { x: 1, y: 0, z: 3 },
+                    instance: a,
+                    connector: 4,
+                },
+            ]
+        );
+        assert!(!report.is_clear());
+
+        // Nothing was placed, and the same placement fails with the first
+        // conflict.
+        assert_eq!(world.instance_count(), 2);
+        let err = world
+            .place_part(&block, WorldCell { x: 0, y: -1, z: 3 })
+            .unwrap_err();
+        assert!(err.contains("occupied"));
+
+        assert!(world
+            .check_placement(&brick, WorldCell { x: 1, y: 0, z: 3 }, Orientation::Deg0)
+            .is_clear());
+    }
+
+    #[test]
+    fn region_queries_span_chunks() {
+        use crate::lattice::Bounds;
+        use crate::world::{World, WorldCell};
+
+        let brick = loader::load_part_dir("assets/parts/lego/3001").unwrap();
+        let mut world = World::new();
+        let near = world.place_part(&brick, WorldCell { x: -1, y: 0, z: 0 }).unwrap();
+        let far = world.place_part(&brick, WorldCell { x: 40, y: 0, z: 0 }).unwrap();
+
+        let region = Bounds { x: [-1, 0], y: [0, 0], z: [0, 0] };
+        assert_eq!(world.cells_in(&region).len(), 2);
+        assert_eq!(world.instances_in(&region).into_iter().collect::<Vec<_>>(), vec![near]);
+
+        let all = Bounds { x: [-100, 100], y: [-100, 100], z: [-100, 100] };
+        assert_eq!(world.instances_in(&all).len(), 2);
+        assert_eq!(world.cells_in(&all).len(), world.occupancy_len());
+
+        world.remove_part(far).unwrap();
+        assert_eq!(world.cells_in(&all).len(), 24);
+    }
+
+    #[test]
+    fn ground_keeps_parts_on_the_build_surface() {
+        use crate::world::{Ground, World, WorldCell};
+
+        let brick = loader::load_part_dir("assets/parts/lego/3003").unwrap();
+        let mut world = World::new();
+        world.set_ground(Some(Ground::Plane { z: 0 }));
+
+        let err = world.place_part(&brick, WorldCell { x: 0, y: 0, z: -1 }).unwrap_err();
+        assert!(err.contains("below ground"));
+        world.place_part(&brick, WorldCell { x: 0, y: 0, z: 0 }).unwrap();
+
+        world.set_ground(Some(Ground::Baseplate { z: 0, x: [0, 7], y: [0, 7] }));
+        let report = world.check_placement(
+            &brick,
+            WorldCell { x: 7, y: 4, z: 0 },
+            crate::orientation::Orientation::Deg0,
+        );
+        assert_eq!(report.below_ground.len(), 6);
+        assert!(report.below_ground.iter().all(|c| c.x == 8));
+        assert!(world.place_part(&brick, WorldCell { x: 6, y: 6, z: 0 }).is_ok());
+    }
 }
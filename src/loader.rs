@@ -14,6 +14,7 @@ pub fn load_part_dir<P: AsRef<Path>>(dir: P) -> Result<Part, String> {
     let dir = dir.as_ref();
     let meta_path = dir.join("part.toml");
     let meta = load_part_meta(&meta_path)?;
+    meta.validate()?;
 
     let lattice_path = dir.join(&meta.lattice.file);
     let connectors_path = dir.join(&meta.connectors.file);
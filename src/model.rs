@@ -0,0 +1,276 @@
+use crate::catalog::Catalog;
+use crate::lattice::Bounds;
+use crate::orientation::Orientation;
+use crate::submodel::{Submodel, SubmodelRef};
+use crate::world::{Ground, PartInstanceId, World, WorldCell};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// Version written by [`save_model`]. Loading rejects anything newer.
+/// Version 2 added submodels, version 3 the placement rules.
+pub const MODEL_VERSION: u32 = 3;
+
+/// On-disk form of a `World`: parts by catalog id, never geometry, so a
+/// model always re-validates against the current catalog.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelFile {
+    pub version: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub submodels: Vec<Submodel>,
+    /// Id the next placed submodel gets, so ids of removed copies are not
+    /// reused. Absent when no submodel was ever placed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_submodel_id: Option<u64>,
+    /// Whether placement checks protruding connectors; on unless saved off.
+    #[serde(default = "enabled", skip_serializing_if = "is_enabled")]
+    pub protrusion_checks: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bounds: Option<Bounds>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ground: Option<Ground>,
+    pub instances: Vec<ModelInstance>,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+fn is_enabled(on: &bool) -> bool {
+    *on
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelInstance {
+    pub id: u64,
+    pub part: String,
+    pub offset: WorldCell,
+    #[serde(default)]
+    pub orientation: Orientation,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub submodel: Option<SubmodelRef>,
+}
+
+impl ModelFile {
+    pub fn from_world(world: &World) -> Self {
+        let instances = world
+            .instances()
+            .map(|(id, inst)| ModelInstance {
+                id: id.0,
+                part: inst.part.clone(),
+                offset: inst.offset,
+                orientation: inst.orientation,
+                color: inst.color.clone(),
+                label: inst.label.clone(),
+                submodel: inst.submodel.clone(),
+            })
+            .collect();
+
+        Self {
+            version: MODEL_VERSION,
+            submodels: world.submodels().cloned().collect(),
+            next_submodel_id: Some(world.next_submodel_id()).filter(|&next| next > 1),
+            protrusion_checks: world.protrusion_checks(),
+            bounds: world.bounds().copied(),
+            ground: world.ground().copied(),
+            instances,
+        }
+    }
+
+    /// Rebuilds the world, placing every instance under its saved id and
+    /// with the saved protrusion checks. Bounds and ground apply once the
+    /// instances are in, since they never re-check placed parts.
+    pub fn to_world(&self, catalog: &Catalog) -> Result<World, String> {
+        if self.version > MODEL_VERSION {
+            return Err(format!(
+                "unsupported model version {} (newest supported is {})",
+                self.version, MODEL_VERSION
+            ));
+        }
+
+        catalog.require_all(self.instances.iter().map(|i| i.part.as_str()))?;
+
+        let mut seen = HashSet::new();
+        let mut world = World::new();
+        world.set_protrusion_checks(self.protrusion_checks);
+        for submodel in &self.submodels {
+            world.define_submodel(submodel.clone())?;
+        }
+
+        for inst in &self.instances {
+            if !seen.insert(inst.id) {
+                return Err(format!("duplicate instance id: {}", inst.id));
+            }
+
+            let id = PartInstanceId(inst.id);
+            let part = catalog.get(&inst.part).expect("checked above");
+            world
+                .place_part_with_id(id, part, inst.offset, inst.orientation, inst.submodel.clone())
+                .map_err(|e| format!("instance {}: {}", inst.id, e))?;
+            world.set_color(id, inst.color.clone())?;
+            world.set_label(id, inst.label.clone())?;
+        }
+
+        if let Some(next) = self.next_submodel_id {
+            world.reserve_submodel_ids(next);
+        }
+        world.set_bounds(self.bounds);
+        world.set_ground(self.ground);
+        world.clear_history()?;
+        Ok(world)
+    }
+}
+
+pub fn save_model<P: AsRef<Path>>(world: &World, path: P) -> Result<(), String> {
+    let path = path.as_ref();
+    let json = serde_json::to_string_pretty(&ModelFile::from_world(world))
+        .map_err(|e| format!("failed to serialize model: {}", e))?;
+
+    fs::write(path, json).map_err(|e| format!("failed to write {}: {}", path.display(), e))
+}
+
+pub fn load_model<P: AsRef<Path>>(path: P, catalog: &Catalog) -> Result<World, String> {
+    let path = path.as_ref();
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+    let model: ModelFile = serde_json::from_str(&content)
+        .map_err(|e| format!("failed to parse {}: {}", path.display(), e))?;
+
+    model.to_world(catalog)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn catalog() -> Catalog {
+        Catalog::load_dir("assets/parts").unwrap()
+    }
+
+    fn sample(catalog: &Catalog) -> World {
+        let mut world = World::new();
+        let base = world
+            .place_part(catalog.get("lego:3001").unwrap(), WorldCell { x: 0, y: 0, z: 0 })
+            .unwrap();
+        let top = world
+            .place_part_oriented(
+                catalog.get("lego:3004").unwrap(),
+                WorldCell { x: 0, y: 0, z: 3 },
+                Orientation::Deg90,
+            )
+            .unwrap();
+        world.set_color(base, Some("red".to_string())).unwrap();
+        world.set_label(top, Some("cap".to_string())).unwrap();
+        world
+    }
+
+    #[test]
+    fn round_trips_through_a_file() {
+        let catalog = catalog();
+        let world = sample(&catalog);
+
+        let path = std::env::temp_dir().join(format!("ssm-model-{}.json", std::process::id()));
+        save_model(&world, &path).unwrap();
+        let loaded = load_model(&path, &catalog).unwrap();
+        let _ = fs::remove_file(&path);
+
+        let before: Vec<_> = world.instances().map(|(id, i)| (id, i.clone())).collect();
+        let after: Vec<_> = loaded.instances().map(|(id, i)| (id, i.clone())).collect();
+        assert_eq!(before, after);
+        assert_eq!(loaded.occupancy_len(), world.occupancy_len());
+        assert!(loaded.connections().is_one_piece());
+        assert!(!loaded.history().can_undo());
+    }
+
+    #[test]
+    fn keeps_placement_rules() {
+        let mut catalog = catalog();
+        let mut smooth = crate::loader::load_part_dir("assets/parts/lego/3001").unwrap();
+        smooth.meta.id = "test:smooth".to_string();
+        smooth.connectors.connectors.clear();
+        catalog.insert(smooth).unwrap();
+
+        // The smooth brick sits on studs, so it only fits without the checks.
+        let mut world = World::new();
+        world.set_protrusion_checks(false);
+        for (part, z) in [("lego:3001", 0), ("test:smooth", 3)] {
+            world.place_part(catalog.get(part).unwrap(), WorldCell { x: 0, y: 0, z }).unwrap();
+        }
+        world.set_bounds(Some(Bounds { x: [0, 9], y: [0, 9], z: [0, 20] }));
+        world.set_ground(Some(Ground::Baseplate { z: 0, x: [0, 9], y: [0, 9] }));
+
+        let json = serde_json::to_string(&ModelFile::from_world(&world)).unwrap();
+        assert!(json.contains(r#""ground":{"kind":"baseplate","z":0,"x":[0,9],"y":[0,9]}"#));
+        let mut model: ModelFile = serde_json::from_str(&json).unwrap();
+        let loaded = model.to_world(&catalog).unwrap();
+        assert!(!loaded.protrusion_checks());
+        assert_eq!(loaded.bounds(), world.bounds());
+        assert_eq!(loaded.ground(), world.ground());
+        assert_eq!(loaded.instance_count(), 2);
+        model.protrusion_checks = true;
+        assert!(model.to_world(&catalog).unwrap_err().contains("protruding"));
+
+        // Like a live world, a loaded one does not re-check placed parts
+        // against its bounds.
+        let mut model = ModelFile::from_world(&sample(&catalog));
+        model.bounds = Some(Bounds { x: [0, 0], y: [0, 0], z: [0, 0] });
+        let loaded = model.to_world(&catalog).unwrap();
+        assert!(loaded.protrusion_checks());
+        let report = loaded.check_placement(
+            catalog.get("lego:3004").unwrap(),
+            WorldCell { x: 5, y: 0, z: 0 },
+            Orientation::Deg0,
+        );
+        assert_eq!(report.out_of_bounds.len(), 6);
+    }
+
+    #[test]
+    fn reports_every_missing_part() {
+        let json = r#"{
+            "version": 1,
+            "instances": [
+                { "id": 1, "part": "lego:9999", "offset": { "x": 0, "y": 0, "z": 0 } },
+                { "id": 2, "part": "lego:3001", "offset": { "x": 5, "y": 0, "z": 0 } },
+                { "id": 3, "part": "lego:1234", "offset": { "x": 9, "y": 0, "z": 0 }, "orientation": 180 }
+            ]
+        }"#;
+        let model: ModelFile = serde_json::from_str(json).unwrap();
+
+        let err = model.to_world(&catalog()).unwrap_err();
+        assert_eq!(err, "missing parts: lego:1234, lego:9999");
+    }
+
+    #[test]
+    fn revalidates_placements() {
+        let catalog = catalog();
+        let mut model = ModelFile::from_world(&sample(&catalog));
+        model.instances[1].offset = WorldCell { x: 0, y: 0, z: 1 };
+
+        let err = model.to_world(&catalog).unwrap_err();
+        assert!(err.starts_with("instance 2:"));
+
+        model.version = MODEL_VERSION + 1;
+        assert!(model.to_world(&catalog).unwrap_err().contains("unsupported"));
+    }
+
+    #[test]
+    fn rejects_ids_with_no_successor() {
+        let catalog = catalog();
+        let mut model = ModelFile::from_world(&sample(&catalog));
+        model.instances[1].id = u64::MAX;
+        let err = model.to_world(&catalog).unwrap_err();
+        assert!(err.starts_with("instance 18446744073709551615:"));
+
+        model.instances[1].id = 2;
+        model.instances[1].submodel = Some(SubmodelRef {
+            id: crate::submodel::SubmodelInstanceId(u64::MAX),
+            name: "cap".to_string(),
+        });
+        assert!(model.to_world(&catalog).unwrap_err().starts_with("instance 2:"));
+    }
+}
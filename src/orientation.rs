@@ -0,0 +1,155 @@
+use crate::connectors::Position;
+use crate::lattice::{Bounds, Cell};
+use crate::rational::Rational;
+use glam::Quat;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::FRAC_PI_2;
+use std::ops::{Neg, Sub};
+
+/// Rotation of a placed part in quarter turns about +Z, counter-clockwise
+/// seen from above. Only these rotations map the stud/plate lattice onto
+/// itself.
+///
+/// Serialized as degrees: `0`, `90`, `180` or `270`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
+#[serde(into = "i32", try_from = "i32")]
+pub enum Orientation {
+    #[default]
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+impl Orientation {
+    pub const ALL: [Orientation; 4] = [
+        Orientation::Deg0,
+        Orientation::Deg90,
+        Orientation::Deg180,
+        Orientation::Deg270,
+    ];
+
+    pub fn quarter_turns(self) -> i32 {
+        match self {
+            Orientation::Deg0 => 0,
+            Orientation::Deg90 => 1,
+            Orientation::Deg180 => 2,
+            Orientation::Deg270 => 3,
+        }
+    }
+
+    pub fn from_quarter_turns(turns: i32) -> Self {
+        Self::ALL[turns.rem_euclid(4) as usize]
+    }
+
+    /// Rotates a cell about the origin. Cells are unit cubes, so the cell
+    /// at `(x, y)` spans `[x, x + 1)` and lands on `(-y - 1, x)` for 90°.
+    /// Works the same for fractional connector positions.
+    pub fn rotate_xy<T>(self, x: T, y: T) -> (T, T)
+    where
+        T: Copy + From<i32> + Neg<Output = T> + Sub<Output = T>,
+    {
+        let one = T::from(1);
+        match self {
+            Orientation::Deg0 => (x, y),
+            Orientation::Deg90 => (-y - one, x),
+            Orientation::Deg180 => (-x - one, -y - one),
+            Orientation::Deg270 => (y, -x - one),
+        }
+    }
+
+    /// Rotates `c` and shifts it back so the rotated `bounds` keep their
+    /// lower-left corner where it was. A part placed at an offset therefore
+    /// always starts at that offset, whatever its orientation.
+    pub fn apply(self, c: Cell, bounds: &Bounds) -> Cell {
+        let (sx, sy) = self.shift(bounds);
+        let (x, y) = self.rotate_xy(c.x, c.y);
+        Cell { x: x + sx, y: y + sy, z: c.z }
+    }
+
+    /// Same as [`Orientation::apply`] for a connector position.
+    pub fn apply_pos(self, p: Position, bounds: &Bounds) -> Position {
+        let (sx, sy) = self.shift(bounds);
+        let (x, y) = self.rotate_xy(p.x, p.y);
+        Position {
+            x: x + Rational::from(sx),
+            y: y + Rational::from(sy),
+            z: p.z,
+        }
+    }
+
+    pub fn rotation(self) -> Quat {
+        Quat::from_rotation_z(self.quarter_turns() as f32 * FRAC_PI_2)
+    }
+
+    pub fn degrees(self) -> i32 {
+        self.quarter_turns() * 90
+    }
+
+    fn shift(self, bounds: &Bounds) -> (i32, i32) {
+        let (ax, ay) = self.rotate_xy(bounds.x[0], bounds.y[0]);
+        let (bx, by) = self.rotate_xy(bounds.x[1], bounds.y[1]);
+        (bounds.x[0] - ax.min(bx), bounds.y[0] - ay.min(by))
+    }
+}
+
+impl From<Orientation> for i32 {
+    fn from(o: Orientation) -> i32 {
+        o.degrees()
+    }
+}
+
+impl TryFrom<i32> for Orientation {
+    type Error = String;
+
+    fn try_from(degrees: i32) -> Result<Self, String> {
+        if degrees.rem_euclid(90) != 0 {
+            return Err(format!("orientation must be a multiple of 90 degrees: {}", degrees));
+        }
+        Ok(Self::from_quarter_turns(degrees / 90))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounds_2x4() -> Bounds {
+        Bounds { x: [0, 1], y: [0, 3], z: [0, 2] }
+    }
+
+    #[test]
+    fn rotation_keeps_lower_corner() {
+        let b = bounds_2x4();
+
+        for o in Orientation::ALL {
+            let cells: Vec<Cell> = [(0, 0), (1, 0), (0, 3), (1, 3)]
+                .iter()
+                .map(|&(x, y)| o.apply(Cell { x, y, z: 0 }, &b))
+                .collect();
+
+            assert_eq!(cells.iter().map(|c| c.x).min(), Some(0));
+            assert_eq!(cells.iter().map(|c| c.y).min(), Some(0));
+        }
+
+        let c = Orientation::Deg90.apply(Cell { x: 1, y: 3, z: 2 }, &b);
+        assert_eq!((c.x, c.y, c.z), (0, 1, 2));
+    }
+
+    #[test]
+    fn fractional_positions_rotate_with_cells() {
+        let b = Bounds { x: [0, 0], y: [0, 1], z: [0, 0] };
+        let center = Position::new(0, Rational::new(1, 2), 1);
+
+        // A jumper stud centred over a 1x2 plate stays centred.
+        let rotated = Orientation::Deg90.apply_pos(center, &b);
+        assert_eq!(rotated, Position::new(Rational::new(1, 2), 0, 1));
+        assert_eq!(Orientation::Deg180.apply_pos(center, &b), center);
+    }
+
+    #[test]
+    fn quarter_turns_compose() {
+        assert_eq!(Orientation::from_quarter_turns(5), Orientation::Deg90);
+        assert_eq!(Orientation::from_quarter_turns(-1), Orientation::Deg270);
+    }
+}
@@ -13,6 +13,14 @@ pub struct PartMeta {
 
     #[serde(default)]
     pub visual: Option<VisualRef>,
+
+    /// Grouping for part lists, e.g. `Brick`.
+    #[serde(default)]
+    pub category: Option<String>,
+
+    /// Mass in grams, used by load estimates. Must be positive.
+    #[serde(default)]
+    pub mass_g: Option<f32>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -33,6 +41,17 @@ pub struct VisualRef {
     pub materials: String,
 }
 
+impl PartMeta {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(mass) = self.mass_g {
+            if !(mass.is_finite() && mass > 0.0) {
+                return Err(format!("mass_g must be positive, not {}", mass));
+            }
+        }
+        Ok(())
+    }
+}
+
 pub fn load_part_meta<P: AsRef<Path>>(path: P) -> Result<PartMeta, String> {
     let content = fs::read_to_string(&path)
         .map_err(|e| format!("failed to read part.toml: {}", e))?;
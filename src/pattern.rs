@@ -0,0 +1,423 @@
+use crate::catalog::Catalog;
+use crate::connectors::{ConnectorType, Position};
+use crate::loader::Part;
+use crate::orientation::Orientation;
+use crate::rational::Rational;
+use crate::world::{cell_bounds, min_corner, Footprint, PartInstanceId, Placement, World, WorldCell};
+use std::collections::HashSet;
+
+/// A lattice axis: the direction a pattern runs or a mirror plane faces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+/// What a pattern repeats, as placed for its first copy.
+#[derive(Clone, Copy)]
+pub enum Stamp<'a> {
+    Part(Placement<'a>),
+    /// A submodel defined in the world being edited.
+    Submodel {
+        name: &'a str,
+        offset: WorldCell,
+        orientation: Orientation,
+    },
+}
+
+/// How copies of a stamp are laid out. Distances are in lattice cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pattern {
+    /// `count` copies, each `step` on from the one before.
+    Linear { count: u32, step: WorldCell },
+    /// `counts` copies along x, y and z, `spacing` apart origin to origin.
+    Grid { counts: [u32; 3], spacing: [i32; 3] },
+    /// A wall of `courses` rows stacked on each other, each `length` copies
+    /// laid end to end along `axis`. Every other course is shifted by half
+    /// a copy so the joints never line up.
+    RunningBond { axis: Axis, length: u32, courses: u32 },
+}
+
+impl Pattern {
+    /// Where each copy goes relative to the first, for a stamp spanning
+    /// `size` cells.
+    pub fn offsets(&self, size: [i32; 3]) -> Result<Vec<WorldCell>, String> {
+        match *self {
+            Pattern::Linear { count, step } => (0..copies(count)?)
+                .map(|i| {
+                    Ok(WorldCell {
+                        x: steps(i, step.x)?,
+                        y: steps(i, step.y)?,
+                        z: steps(i, step.z)?,
+                    })
+                })
+                .collect(),
+            Pattern::Grid { counts, spacing } => {
+                let [nx, ny, nz] = [copies(counts[0])?, copies(counts[1])?, copies(counts[2])?];
+                let mut out = Vec::new();
+                for z in 0..nz {
+                    for y in 0..ny {
+                        for x in 0..nx {
+                            out.push(WorldCell {
+                                x: steps(x, spacing[0])?,
+                                y: steps(y, spacing[1])?,
+                                z: steps(z, spacing[2])?,
+                            });
+                        }
+                    }
+                }
+                Ok(out)
+            }
+            Pattern::RunningBond { axis, length, courses } => {
+                let run = match axis {
+                    Axis::X => size[0],
+                    Axis::Y => size[1],
+                    Axis::Z => return Err("running bond must run along x or y".to_string()),
+                };
+                if run % 2 != 0 {
+                    return Err(format!("running bond needs an even length, not {}", run));
+                }
+
+                let mut out = Vec::new();
+                for course in 0..copies(courses)? {
+                    let shift = (course % 2) * run / 2;
+                    for i in 0..copies(length)? {
+                        let along = steps(i, run)?
+                            .checked_add(shift)
+                            .ok_or_else(|| too_far(i, run))?;
+                        let (x, y) = match axis {
+                            Axis::X => (along, 0),
+                            _ => (0, along),
+                        };
+                        out.push(WorldCell { x, y, z: steps(course, size[2])? });
+                    }
+                }
+                Ok(out)
+            }
+        }
+    }
+}
+
+fn copies(count: u32) -> Result<i32, String> {
+    i32::try_from(count).map_err(|_| format!("pattern has too many copies: {}", count))
+}
+
+/// How far `n` steps of `step` cells reach, if that fits the lattice.
+fn steps(n: i32, step: i32) -> Result<i32, String> {
+    n.checked_mul(step).ok_or_else(|| too_far(n, step))
+}
+
+fn too_far(n: i32, step: i32) -> String {
+    format!("pattern runs off the lattice: {} steps of {} cells", n, step)
+}
+
+/// Places a copy of `stamp` at every offset of `pattern`, all or none, as a
+/// single undo step. Returns the placed part instances in copy order.
+pub fn place_pattern(
+    world: &mut World,
+    catalog: &Catalog,
+    stamp: &Stamp<'_>,
+    pattern: &Pattern,
+) -> Result<Vec<PartInstanceId>, String> {
+    let offsets = pattern.offsets(stamp_size(world, catalog, stamp)?)?;
+    if offsets.is_empty() {
+        return Err("pattern places nothing".to_string());
+    }
+
+    world.transaction(|world| {
+        let mut placed = Vec::new();
+        for (i, by) in offsets.iter().enumerate() {
+            let shifted = |offset: WorldCell| {
+                offset.checked_offset(*by).ok_or_else(|| format!("offset {:?} is out of range", by))
+            };
+            let copy = match *stamp {
+                Stamp::Part(p) => shifted(p.offset).and_then(|offset| {
+                    world.place_part_oriented(p.part, offset, p.orientation).map(|id| vec![id])
+                }),
+                Stamp::Submodel { name, offset, orientation } => {
+                    shifted(offset).and_then(|offset| {
+                        world
+                            .place_submodel(name, catalog, offset, orientation)
+                            .map(|id| world.submodel_parts(id))
+                    })
+                }
+            };
+            placed.extend(copy.map_err(|e| format!("copy {}: {}", i, e))?);
+        }
+        Ok(placed)
+    })
+}
+
+fn stamp_size(world: &World, catalog: &Catalog, stamp: &Stamp<'_>) -> Result<[i32; 3], String> {
+    let cells: Vec<WorldCell> = match *stamp {
+        Stamp::Part(p) => Footprint::new(p.part, p.offset, p.orientation).cells,
+        Stamp::Submodel { name, offset, orientation } => world
+            .submodel(name)
+            .ok_or_else(|| format!("unknown submodel: {}", name))?
+            .resolve(catalog, offset, orientation)?
+            .iter()
+            .flat_map(|p| Footprint::new(p.part, p.offset, p.orientation).cells)
+            .collect(),
+    };
+
+    let b = cell_bounds(cells.iter()).ok_or("stamp has no cells")?;
+    Ok([
+        b.x[1] - b.x[0] + 1,
+        b.y[1] - b.y[0] + 1,
+        b.z[1] - b.z[0] + 1,
+    ])
+}
+
+/// Places mirror images of `ids` across the plane `axis = plane`, where
+/// `plane` is a cell boundary, all or none as a single undo step. Parts
+/// are never flipped, so each must have a turn that looks like its own
+/// mirror image; the copies keep their originals' colors but are loose
+/// parts, even when the originals belong to a submodel.
+pub fn mirror_instances(
+    world: &mut World,
+    catalog: &Catalog,
+    ids: &[PartInstanceId],
+    axis: Axis,
+    plane: i32,
+) -> Result<Vec<PartInstanceId>, String> {
+    let mut instances = Vec::new();
+    for &id in ids {
+        let inst = world
+            .instance(id)
+            .ok_or_else(|| format!("unknown instance: {:?}", id))?;
+        instances.push((id, inst));
+    }
+
+    catalog.require_all(instances.iter().map(|(_, inst)| inst.part.as_str()))?;
+
+    let mut copies = Vec::new();
+    for (id, inst) in instances {
+        let part = catalog.get(&inst.part).expect("checked above");
+        let footprint = world.footprint(id).expect("instance has a footprint");
+        let (offset, orientation) = mirror_placement(part, footprint, axis, plane)
+            .ok_or_else(|| format!("{} has no mirror image: {:?}", inst.part, id))?;
+        copies.push((part, offset, orientation, inst.color.clone()));
+    }
+
+    world.transaction(|world| {
+        copies
+            .into_iter()
+            .enumerate()
+            .map(|(i, (part, offset, orientation, color))| {
+                let id = world
+                    .place_part_oriented(part, offset, orientation)
+                    .map_err(|e| format!("mirror {}: {}", i, e))?;
+                world.set_color(id, color)?;
+                Ok(id)
+            })
+            .collect()
+    })
+}
+
+/// The placement of `part` whose cells and connectors are the reflection
+/// of `footprint`, if some turn of the part has that shape.
+fn mirror_placement(
+    part: &Part,
+    footprint: &Footprint,
+    axis: Axis,
+    plane: i32,
+) -> Option<(WorldCell, Orientation)> {
+    let cell = |c: WorldCell| {
+        let m = 2 * plane - 1;
+        match axis {
+            Axis::X => WorldCell { x: m - c.x, ..c },
+            Axis::Y => WorldCell { y: m - c.y, ..c },
+            Axis::Z => WorldCell { z: m - c.z, ..c },
+        }
+    };
+    // Connector positions turn like cells do, so they mirror like cells too.
+    let pos = |p: Position| {
+        let m = Rational::from(2 * plane - 1);
+        match axis {
+            Axis::X => Position { x: m - p.x, ..p },
+            Axis::Y => Position { y: m - p.y, ..p },
+            Axis::Z => Position { z: m - p.z, ..p },
+        }
+    };
+
+    let want_cells: HashSet<WorldCell> = footprint.cells.iter().map(|&c| cell(c)).collect();
+    let want_connectors: HashSet<_> = footprint
+        .connectors
+        .iter()
+        .map(|c| {
+            let mut n = c.normal;
+            match axis {
+                Axis::X => n.x = -n.x,
+                Axis::Y => n.y = -n.y,
+                Axis::Z => n.z = -n.z,
+            }
+            connector_key(c.kind, pos(c.pos), n.to_array())
+        })
+        .collect();
+    let want_min = min_corner(want_cells.iter().copied());
+
+    Orientation::ALL
+        .into_iter()
+        .find_map(|orientation| {
+            let at_origin = Footprint::new(part, WorldCell { x: 0, y: 0, z: 0 }, orientation);
+            let have_min = min_corner(at_origin.cells.iter().copied());
+            let offset = WorldCell {
+                x: want_min.x - have_min.x,
+                y: want_min.y - have_min.y,
+                z: want_min.z - have_min.z,
+            };
+            let candidate = at_origin.translated(offset);
+
+            let cells: HashSet<WorldCell> = candidate.cells.iter().copied().collect();
+            let connectors: HashSet<_> = candidate
+                .connectors
+                .iter()
+                .map(|c| connector_key(c.kind, c.pos, c.normal.to_array()))
+                .collect();
+            (cells == want_cells && connectors == want_connectors).then_some((offset, orientation))
+        })
+}
+
+fn connector_key(
+    kind: ConnectorType,
+    pos: Position,
+    normal: [f32; 3],
+) -> (ConnectorType, Position, [i32; 3]) {
+    (kind, pos, normal.map(|v| (v * 1000.0).round() as i32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader::load_part_dir;
+    use crate::submodel::Submodel;
+
+    fn at(x: i32, y: i32, z: i32) -> WorldCell {
+        WorldCell { x, y, z }
+    }
+
+    fn brick(part: &Part, offset: WorldCell) -> Stamp<'_> {
+        Stamp::Part(Placement { part, offset, orientation: Orientation::Deg0 })
+    }
+
+    #[test]
+    fn lays_out_rows_grids_and_bonds() {
+        let row = Pattern::Linear { count: 3, step: at(0, 4, 0) };
+        assert_eq!(row.offsets([2, 4, 3]).unwrap(), vec![at(0, 0, 0), at(0, 4, 0), at(0, 8, 0)]);
+
+        let grid = Pattern::Grid { counts: [2, 2, 1], spacing: [3, 5, 0] };
+        assert_eq!(
+            grid.offsets([2, 4, 3]).unwrap(),
+            vec![at(0, 0, 0), at(3, 0, 0), at(0, 5, 0), at(3, 5, 0)]
+        );
+
+        let bond = Pattern::RunningBond { axis: Axis::Y, length: 2, courses: 2 };
+        assert_eq!(
+            bond.offsets([2, 4, 3]).unwrap(),
+            vec![at(0, 0, 0), at(0, 4, 0), at(0, 2, 3), at(0, 6, 3)]
+        );
+        assert_eq!(
+            bond.offsets([2, 3, 3]).unwrap_err(),
+            "running bond needs an even length, not 3"
+        );
+        let up = Pattern::RunningBond { axis: Axis::Z, length: 2, courses: 2 };
+        assert!(up.offsets([2, 4, 3]).is_err());
+
+        let far = Pattern::Linear { count: 3, step: at(i32::MAX / 2 + 10, 0, 0) };
+        assert_eq!(
+            far.offsets([2, 4, 3]).unwrap_err(),
+            "pattern runs off the lattice: 2 steps of 1073741833 cells"
+        );
+        let many = Pattern::Grid { counts: [1, 1, u32::MAX], spacing: [0, 0, 0] };
+        assert_eq!(many.offsets([2, 4, 3]).unwrap_err(), "pattern has too many copies: 4294967295");
+    }
+
+    #[test]
+    fn places_a_wall_as_one_batch() {
+        let catalog = Catalog::load_dir("assets/parts").unwrap();
+        let part = catalog.get("lego:3001").unwrap();
+        let mut world = World::new();
+
+        let wall = Pattern::RunningBond { axis: Axis::Y, length: 3, courses: 3 };
+        let ids = place_pattern(&mut world, &catalog, &brick(part, at(0, 0, 0)), &wall).unwrap();
+        assert_eq!(ids.len(), 9);
+        assert!(world.connections().is_one_piece());
+        assert_eq!(world.instance(ids[3]).unwrap().offset, at(0, 2, 3));
+
+        // The last copy runs into the wall, so none of the row lands.
+        let row = Pattern::Linear { count: 3, step: at(-2, 0, 0) };
+        let err = place_pattern(&mut world, &catalog, &brick(part, at(4, 0, 0)), &row).unwrap_err();
+        assert!(err.starts_with("copy 2:"), "{}", err);
+        assert_eq!(world.instance_count(), 9);
+
+        world.undo().unwrap();
+        assert_eq!(world.instance_count(), 0);
+    }
+
+    #[test]
+    fn repeats_submodels() {
+        let catalog = Catalog::load_dir("assets/parts").unwrap();
+        let mut pillar = World::new();
+        pillar.place_part(catalog.get("lego:3001").unwrap(), at(0, 0, 0)).unwrap();
+        pillar.place_part(catalog.get("lego:3004").unwrap(), at(0, 2, 3)).unwrap();
+
+        let mut world = World::new();
+        world.define_submodel(Submodel::from_world("pillar", &pillar)).unwrap();
+        let stamp = Stamp::Submodel {
+            name: "pillar",
+            offset: at(0, 0, 0),
+            orientation: Orientation::Deg0,
+        };
+        let grid = Pattern::Grid { counts: [3, 1, 1], spacing: [3, 0, 0] };
+
+        let ids = place_pattern(&mut world, &catalog, &stamp, &grid).unwrap();
+        assert_eq!(ids.len(), 6);
+        assert_eq!(world.submodel_instances().len(), 3);
+        assert_eq!(world.instance(ids[4]).unwrap().offset, at(6, 0, 0));
+    }
+
+    #[test]
+    fn mirrors_symmetric_parts_only() {
+        let mut catalog = Catalog::load_dir("assets/parts").unwrap();
+        let mut chipped = load_part_dir("assets/parts/lego/3001").unwrap();
+        chipped.meta.id = "test:chipped".to_string();
+        let stud = chipped
+            .connectors
+            .connectors
+            .iter()
+            .position(|c| c.kind == ConnectorType::Stud)
+            .unwrap();
+        chipped.connectors.connectors.remove(stud);
+        catalog.insert(chipped).unwrap();
+
+        let mut world = World::new();
+        let base = world.place_part(catalog.get("lego:3001").unwrap(), at(0, 0, 0)).unwrap();
+        let cap = world
+            .place_part_oriented(catalog.get("lego:3004").unwrap(), at(0, 1, 3), Orientation::Deg90)
+            .unwrap();
+        world.set_color(cap, Some("red".to_string())).unwrap();
+
+        let copies = mirror_instances(&mut world, &catalog, &[base, cap], Axis::X, 4).unwrap();
+        assert_eq!(world.instance(copies[0]).unwrap().offset, at(6, 0, 0));
+        let copy = world.instance(copies[1]).unwrap();
+        assert_eq!(copy.color.as_deref(), Some("red"));
+        let cells: HashSet<_> = world.instance_cells(copies[1]).unwrap().iter().copied().collect();
+        let expected: HashSet<_> = world
+            .instance_cells(cap)
+            .unwrap()
+            .iter()
+            .map(|c| at(7 - c.x, c.y, c.z))
+            .collect();
+        assert_eq!(cells, expected);
+        assert_eq!(world.connections().connected_components().len(), 2);
+
+        let odd = world.place_part(catalog.get("test:chipped").unwrap(), at(0, 10, 0)).unwrap();
+        let err = mirror_instances(&mut world, &Catalog::new(), &[base, odd], Axis::X, 4);
+        assert_eq!(err.unwrap_err(), "missing parts: lego:3001, test:chipped");
+
+        let err = mirror_instances(&mut world, &catalog, &[odd], Axis::X, 4).unwrap_err();
+        assert!(err.starts_with("test:chipped has no mirror image"), "{}", err);
+        assert_eq!(world.instance_count(), 5);
+    }
+}
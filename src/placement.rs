@@ -0,0 +1,63 @@
+use crate::world::{PartInstanceId, WorldCell};
+
+/// A protruding connector that is not taken by a mating one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProtrusionConflict {
+    /// A connector of `instance` reaches into a cell the placement fills.
+    Blocked {
+        cell: WorldCell,
+        instance: PartInstanceId,
+        connector: usize,
+    },
+    /// A connector of the placed part reaches into `instance`.
+    Intrudes {
+        cell: WorldCell,
+        connector: usize,
+        instance: PartInstanceId,
+    },
+}
+
+/// Everything standing in the way of a placement, as found by
+/// `World::check_placement` without touching the world.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlacementReport {
+    /// Cells already taken, with the instance taking each.
+    pub collisions: Vec<(WorldCell, PartInstanceId)>,
+    /// Cells outside the world bounds.
+    pub out_of_bounds: Vec<WorldCell>,
+    /// Cells under the ground or off the edge of the baseplate.
+    pub below_ground: Vec<WorldCell>,
+    pub unmatched_protrusions: Vec<ProtrusionConflict>,
+}
+
+impl PlacementReport {
+    pub fn is_clear(&self) -> bool {
+        self.collisions.is_empty()
+            && self.out_of_bounds.is_empty()
+            && self.below_ground.is_empty()
+            && self.unmatched_protrusions.is_empty()
+    }
+
+    /// The first conflict as the error `place_part` reports for it.
+    pub fn first_error(&self) -> Option<String> {
+        if let Some((cell, _)) = self.collisions.first() {
+            return Some(format!("cell already occupied: {:?}", cell));
+        }
+        if let Some(cell) = self.out_of_bounds.first() {
+            return Some(format!("cell out of bounds: {:?}", cell));
+        }
+        if let Some(cell) = self.below_ground.first() {
+            return Some(format!("cell below ground: {:?}", cell));
+        }
+        self.unmatched_protrusions.first().map(|p| match *p {
+            ProtrusionConflict::Blocked { cell, instance, connector } => format!(
+                "cell blocked by protruding connector {} of {:?}: {:?}",
+                connector, instance, cell
+            ),
+            ProtrusionConflict::Intrudes { cell, connector, instance } => format!(
+                "protruding connector {} runs into {:?}: {:?}",
+                connector, instance, cell
+            ),
+        })
+    }
+}
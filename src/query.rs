@@ -0,0 +1,471 @@
+use crate::bom::color_key;
+use crate::catalog::Catalog;
+use crate::lattice::{Bounds, Cell};
+use crate::model::ModelFile;
+use crate::world::{PartInstance, PartInstanceId, World};
+use std::collections::{BTreeSet, VecDeque};
+use std::str::FromStr;
+
+/// A set of placed instances, e.g. the result of a [`Query`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Selection {
+    ids: BTreeSet<PartInstanceId>,
+}
+
+impl FromIterator<PartInstanceId> for Selection {
+    fn from_iter<I: IntoIterator<Item = PartInstanceId>>(iter: I) -> Self {
+        Self { ids: iter.into_iter().collect() }
+    }
+}
+
+impl Selection {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every instance in `world`.
+    pub fn all(world: &World) -> Self {
+        world.instances().map(|(id, _)| id).collect()
+    }
+
+    pub fn insert(&mut self, id: PartInstanceId) -> bool {
+        self.ids.insert(id)
+    }
+
+    pub fn remove(&mut self, id: PartInstanceId) -> bool {
+        self.ids.remove(&id)
+    }
+
+    pub fn contains(&self, id: PartInstanceId) -> bool {
+        self.ids.contains(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// Ids in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = PartInstanceId> + '_ {
+        self.ids.iter().copied()
+    }
+
+    /// Ids in ascending order, as editing commands take them.
+    pub fn ids(&self) -> Vec<PartInstanceId> {
+        self.iter().collect()
+    }
+
+    pub fn union(&self, other: &Selection) -> Selection {
+        self.ids.union(&other.ids).copied().collect()
+    }
+
+    pub fn intersection(&self, other: &Selection) -> Selection {
+        self.ids.intersection(&other.ids).copied().collect()
+    }
+
+    pub fn difference(&self, other: &Selection) -> Selection {
+        self.ids.difference(&other.ids).copied().collect()
+    }
+
+    /// Drops ids no longer in `world`, e.g. after an undo.
+    pub fn retain_existing(&mut self, world: &World) {
+        self.ids.retain(|id| world.instance(*id).is_some());
+    }
+
+    /// A new world holding only the selected instances, under their own
+    /// ids, with the source's placement rules and submodel definitions.
+    /// Exporters such as the bill of materials can then run on it as on
+    /// any other world.
+    pub fn extract(&self, world: &World, catalog: &Catalog) -> Result<World, String> {
+        let mut model = ModelFile::from_world(world);
+        model.instances.retain(|i| self.contains(PartInstanceId(i.id)));
+
+        model.to_world(catalog)
+    }
+}
+
+/// One condition on a placed instance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Filter {
+    /// Catalog id, e.g. `lego:3001`.
+    Part(String),
+    System(String),
+    /// Color name, compared by [`color_key`] as the bill of materials
+    /// groups colors. `None` matches parts left in their default material.
+    Color(Option<String>),
+    Label(String),
+    /// Every cell of the instance lies in the region.
+    Within(Bounds),
+    /// Some cell of the instance lies in the region.
+    Touches(Bounds),
+    /// The instance's lowest cell sits between these layers, inclusive.
+    Layer { min: Option<i32>, max: Option<i32> },
+    /// Joined to the instance through any chain of connections, itself
+    /// included.
+    ConnectedTo(PartInstanceId),
+    Any(Vec<Filter>),
+    Not(Box<Filter>),
+}
+
+impl Filter {
+    /// Lowest cell above layer `z`. Nothing sits above the topmost layer,
+    /// so there this matches nothing.
+    pub fn above(z: i32) -> Filter {
+        match z.checked_add(1) {
+            Some(min) => Filter::Layer { min: Some(min), max: None },
+            None => Filter::Any(Vec::new()),
+        }
+    }
+
+    /// Lowest cell below layer `z`; like [`Filter::above`], nothing passes
+    /// at the bottommost layer.
+    pub fn below(z: i32) -> Filter {
+        match z.checked_sub(1) {
+            Some(max) => Filter::Layer { min: None, max: Some(max) },
+            None => Filter::Any(Vec::new()),
+        }
+    }
+
+    /// Instances of `world` that pass.
+    pub fn select(&self, world: &World) -> Selection {
+        match self {
+            Filter::Part(part) => matching(world, |inst| inst.part == *part),
+            Filter::System(system) => matching(world, |inst| inst.system == *system),
+            Filter::Color(color) => matching(world, |inst| {
+                match (&inst.color, color) {
+                    (Some(have), Some(want)) => color_key(have) == color_key(want),
+                    (have, want) => have.is_none() && want.is_none(),
+                }
+            }),
+            Filter::Label(label) => {
+                matching(world, |inst| inst.label.as_deref() == Some(label.as_str()))
+            }
+            Filter::Within(region) => world
+                .instances_in(region)
+                .into_iter()
+                .filter(|id| {
+                    world.instance_cells(*id).is_some_and(|cells| {
+                        cells.iter().all(|c| region.contains(&Cell { x: c.x, y: c.y, z: c.z }))
+                    })
+                })
+                .collect(),
+            Filter::Touches(region) => world.instances_in(region).into_iter().collect(),
+            Filter::Layer { min, max } => world
+                .instances()
+                .filter(|(id, _)| {
+                    world.bottom_layer(*id).is_some_and(|z| {
+                        min.is_none_or(|min| z >= min) && max.is_none_or(|max| z <= max)
+                    })
+                })
+                .map(|(id, _)| id)
+                .collect(),
+            Filter::ConnectedTo(start) => connected(world, *start),
+            Filter::Any(filters) => filters
+                .iter()
+                .fold(Selection::new(), |acc, f| acc.union(&f.select(world))),
+            Filter::Not(filter) => Selection::all(world).difference(&filter.select(world)),
+        }
+    }
+}
+
+fn matching(world: &World, keep: impl Fn(&PartInstance) -> bool) -> Selection {
+    world
+        .instances()
+        .filter(|(_, inst)| keep(inst))
+        .map(|(id, _)| id)
+        .collect()
+}
+
+fn connected(world: &World, start: PartInstanceId) -> Selection {
+    let mut seen = Selection::new();
+    if world.instance(start).is_none() {
+        return seen;
+    }
+
+    let mut queue = VecDeque::from([start]);
+    seen.insert(start);
+    while let Some(id) = queue.pop_front() {
+        for next in world.connections().neighbors(id) {
+            if seen.insert(next) {
+                queue.push_back(next);
+            }
+        }
+    }
+    seen
+}
+
+/// Instances passing every filter. Build one with the methods below or
+/// parse it from text:
+///
+/// ```text
+/// part=lego:3001 color=red layer>6
+/// in=0,0,0..9,9,20 !label=door
+/// color=red|blue connected=4
+/// ```
+///
+/// Terms are separated by spaces and must all hold. `key=a|b` accepts any
+/// of the values and a leading `!` negates a term. Keys are `part`,
+/// `system`, `color` (`none` for the default material), `label`, `in` and
+/// `touches` (a region given by two inclusive corners), `connected` (an
+/// instance id) and `layer` with `=`, `<`, `<=`, `>` or `>=`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Query {
+    pub filters: Vec<Filter>,
+}
+
+impl Query {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filters.push(filter);
+        self
+    }
+
+    pub fn part(self, part: &str) -> Self {
+        self.filter(Filter::Part(part.to_string()))
+    }
+
+    pub fn system(self, system: &str) -> Self {
+        self.filter(Filter::System(system.to_string()))
+    }
+
+    pub fn color(self, color: &str) -> Self {
+        self.filter(Filter::Color(Some(color.to_string())))
+    }
+
+    pub fn label(self, label: &str) -> Self {
+        self.filter(Filter::Label(label.to_string()))
+    }
+
+    pub fn within(self, region: Bounds) -> Self {
+        self.filter(Filter::Within(region))
+    }
+
+    pub fn touching(self, region: Bounds) -> Self {
+        self.filter(Filter::Touches(region))
+    }
+
+    /// Instances whose lowest cell is above layer `z`.
+    pub fn above(self, z: i32) -> Self {
+        self.filter(Filter::above(z))
+    }
+
+    /// Instances whose lowest cell is below layer `z`.
+    pub fn below(self, z: i32) -> Self {
+        self.filter(Filter::below(z))
+    }
+
+    pub fn on_layer(self, z: i32) -> Self {
+        self.filter(Filter::Layer { min: Some(z), max: Some(z) })
+    }
+
+    pub fn connected_to(self, id: PartInstanceId) -> Self {
+        self.filter(Filter::ConnectedTo(id))
+    }
+
+    /// Runs the query. With no filters it selects everything.
+    pub fn run(&self, world: &World) -> Selection {
+        self.filters
+            .iter()
+            .fold(Selection::all(world), |acc, f| acc.intersection(&f.select(world)))
+    }
+}
+
+impl FromStr for Query {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let filters = s.split_whitespace().map(parse_term).collect::<Result<_, _>>()?;
+        Ok(Query { filters })
+    }
+}
+
+fn parse_term(term: &str) -> Result<Filter, String> {
+    if let Some(rest) = term.strip_prefix('!') {
+        return Ok(Filter::Not(Box::new(parse_term(rest)?)));
+    }
+
+    if let Some(rest) = term.strip_prefix("layer") {
+        return parse_layer(rest).ok_or_else(|| format!("bad layer term: {}", term));
+    }
+
+    let (key, value) = term
+        .split_once('=')
+        .ok_or_else(|| format!("expected key=value: {}", term))?;
+    if value.is_empty() {
+        return Err(format!("missing value: {}", term));
+    }
+
+    let one = |value: &str| -> Result<Filter, String> {
+        Ok(match key {
+            "part" => Filter::Part(value.to_string()),
+            "system" => Filter::System(value.to_string()),
+            "color" if value.eq_ignore_ascii_case("none") => Filter::Color(None),
+            "color" => Filter::Color(Some(value.to_string())),
+            "label" => Filter::Label(value.to_string()),
+            "in" => Filter::Within(parse_region(value)?),
+            "touches" => Filter::Touches(parse_region(value)?),
+            "connected" => Filter::ConnectedTo(PartInstanceId(
+                value.parse().map_err(|_| format!("bad instance id: {}", value))?,
+            )),
+            _ => return Err(format!("unknown query key: {}", key)),
+        })
+    };
+
+    let mut alternatives = value.split('|').map(one).collect::<Result<Vec<_>, _>>()?;
+    if alternatives.len() == 1 {
+        Ok(alternatives.remove(0))
+    } else {
+        Ok(Filter::Any(alternatives))
+    }
+}
+
+fn parse_layer(rest: &str) -> Option<Filter> {
+    let layer = |z: &str| z.parse::<i32>().ok();
+    Some(if let Some(z) = rest.strip_prefix(">=") {
+        Filter::Layer { min: Some(layer(z)?), max: None }
+    } else if let Some(z) = rest.strip_prefix("<=") {
+        Filter::Layer { min: None, max: Some(layer(z)?) }
+    } else if let Some(z) = rest.strip_prefix('>') {
+        Filter::above(layer(z)?)
+    } else if let Some(z) = rest.strip_prefix('<') {
+        Filter::below(layer(z)?)
+    } else {
+        let z = layer(rest.strip_prefix('=')?)?;
+        Filter::Layer { min: Some(z), max: Some(z) }
+    })
+}
+
+/// `x0,y0,z0..x1,y1,z1`, corners in either order.
+fn parse_region(value: &str) -> Result<Bounds, String> {
+    let corner = |s: &str| -> Option<[i32; 3]> {
+        let v: Vec<i32> = s.split(',').map(|n| n.parse().ok()).collect::<Option<_>>()?;
+        v.try_into().ok()
+    };
+    let (a, b) = value
+        .split_once("..")
+        .and_then(|(a, b)| Some((corner(a)?, corner(b)?)))
+        .ok_or_else(|| format!("bad region: {}", value))?;
+
+    Ok(Bounds {
+        x: [a[0].min(b[0]), a[0].max(b[0])],
+        y: [a[1].min(b[1]), a[1].max(b[1])],
+        z: [a[2].min(b[2]), a[2].max(b[2])],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bom::BillOfMaterials;
+    use crate::world::WorldCell;
+
+    /// Two red 2x4s stacked, a blue 2x2 on top, and a loose labelled 1x2.
+    fn sample() -> (World, Catalog, Vec<PartInstanceId>) {
+        let catalog = Catalog::load_dir("assets/parts").unwrap();
+        let mut world = World::new();
+        let mut place = |part: &str, x, y, z, color: Option<&str>| {
+            let id = world
+                .place_part(catalog.get(part).unwrap(), WorldCell { x, y, z })
+                .unwrap();
+            world.set_color(id, color.map(str::to_string)).unwrap();
+            id
+        };
+        let ids = vec![
+            place("lego:3001", 0, 0, 0, Some("red")),
+            place("lego:3001", 0, 0, 3, Some("Red")),
+            place("lego:3003", 0, 0, 6, Some("blue")),
+            place("lego:3004", 10, 0, 0, None),
+        ];
+        world.set_label(ids[3], Some("door".to_string())).unwrap();
+        (world, catalog, ids)
+    }
+
+    #[test]
+    fn filters_with_the_builder() {
+        let (world, _, ids) = sample();
+        let select = |q: Query| q.run(&world).ids();
+
+        assert_eq!(select(Query::new()), ids);
+        assert_eq!(select(Query::new().part("lego:3001").color("RED").above(2)), vec![ids[1]]);
+        assert_eq!(select(Query::new().on_layer(0)), vec![ids[0], ids[3]]);
+        assert_eq!(select(Query::new().below(6).system("lego")), vec![ids[0], ids[1], ids[3]]);
+        assert_eq!(select(Query::new().label("door")), vec![ids[3]]);
+        assert_eq!(select(Query::new().above(i32::MAX)), vec![]);
+        assert_eq!(select(Query::new().below(i32::MIN)), vec![]);
+        assert_eq!(select(Query::new().above(i32::MIN)), ids);
+        assert_eq!(select(Query::new().filter(Filter::Color(None))), vec![ids[3]]);
+        assert_eq!(select(Query::new().color(" red ")), vec![ids[0], ids[1]]);
+
+        let region = Bounds { x: [0, 1], y: [0, 1], z: [0, 8] };
+        assert_eq!(select(Query::new().within(region)), vec![ids[2]]);
+        assert_eq!(select(Query::new().touching(region)), vec![ids[0], ids[1], ids[2]]);
+
+        assert_eq!(select(Query::new().connected_to(ids[2])), vec![ids[0], ids[1], ids[2]]);
+        assert_eq!(select(Query::new().connected_to(PartInstanceId(99))), vec![]);
+    }
+
+    #[test]
+    fn parses_text_queries() {
+        let (world, _, ids) = sample();
+        let select = |q: &str| q.parse::<Query>().unwrap().run(&world).ids();
+
+        assert_eq!(select("part=lego:3001 color=red layer>2"), vec![ids[1]]);
+        assert_eq!(select("in=1,1,8..0,0,0"), vec![ids[2]]);
+        assert_eq!(select("touches=0,0,0..1,1,8 !color=blue"), vec![ids[0], ids[1]]);
+        assert_eq!(select("color=blue|none"), vec![ids[2], ids[3]]);
+        assert_eq!(select("layer<=3 connected=1"), vec![ids[0], ids[1]]);
+        assert_eq!(select("layer=6"), vec![ids[2]]);
+        assert_eq!(select("  "), ids);
+
+        // Text and builder agree at the edges of the lattice.
+        for (text, built) in [
+            ("layer>2147483647", Query::new().above(i32::MAX)),
+            ("layer<-2147483648", Query::new().below(i32::MIN)),
+            ("layer>-2147483648", Query::new().above(i32::MIN)),
+        ] {
+            assert_eq!(text.parse::<Query>().unwrap(), built);
+        }
+        assert_eq!(select("layer>2147483647"), vec![]);
+        assert_eq!(select("layer<-2147483648"), vec![]);
+
+        let err = |q: &str| q.parse::<Query>().unwrap_err();
+        assert_eq!(err("shape=round"), "unknown query key: shape");
+        assert_eq!(err("layer>>2"), "bad layer term: layer>>2");
+        assert_eq!(err("layer>2147483648"), "bad layer term: layer>2147483648");
+        assert_eq!(err("in=0,0..1,1,1"), "bad region: 0,0..1,1,1");
+        assert_eq!(err("red"), "expected key=value: red");
+    }
+
+    #[test]
+    fn selections_feed_edits_and_exports() {
+        let (mut world, catalog, ids) = sample();
+        let red = Query::new().color("red").run(&world);
+        let low = "layer<3".parse::<Query>().unwrap().run(&world);
+
+        assert_eq!(red.union(&low).len(), 3);
+        assert_eq!(red.intersection(&low).ids(), vec![ids[0]]);
+        assert_eq!(low.difference(&red).ids(), vec![ids[3]]);
+
+        let part = red.extract(&world, &catalog).unwrap();
+        let bom = BillOfMaterials::from_world(&part, &catalog).unwrap();
+        assert_eq!(bom.total(), 2);
+        assert_eq!(part.instance(ids[1]).unwrap().color.as_deref(), Some("Red"));
+
+        world.remove_parts(&red.ids()).unwrap();
+        assert_eq!(world.instance_count(), 2);
+        assert!(world.remove_parts(&[ids[2], ids[0]]).is_err());
+        assert_eq!(world.instance_count(), 2);
+
+        let mut stale = Selection::all(&world).union(&red);
+        stale.retain_existing(&world);
+        assert_eq!(stale.ids(), vec![ids[2], ids[3]]);
+        world.undo().unwrap();
+        assert_eq!(world.instance_count(), 4);
+    }
+}
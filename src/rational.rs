@@ -0,0 +1,251 @@
+use serde::Deserialize;
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Neg, Sub};
+use std::str::FromStr;
+
+/// Exact fraction of a lattice unit, always stored in lowest terms with a
+/// positive denominator so equal values compare and hash equal.
+///
+/// In JSON a value is either an integer (`3`) or a fraction string
+/// (`"1/2"`, `"-5/3"`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(try_from = "RawRational")]
+pub struct Rational {
+    num: i64,
+    den: i64,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawRational {
+    Integer(i64),
+    Fraction(String),
+}
+
+impl TryFrom<RawRational> for Rational {
+    type Error = String;
+
+    fn try_from(raw: RawRational) -> Result<Self, String> {
+        match raw {
+            RawRational::Integer(n) => Ok(Rational::integer(n)),
+            RawRational::Fraction(s) => s.parse(),
+        }
+    }
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.abs(), b.abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+impl Rational {
+    pub const ZERO: Rational = Rational { num: 0, den: 1 };
+
+    /// Panics if `den` is zero or the reduced fraction does not fit in
+    /// `i64`; see [`Rational::checked_new`].
+    pub fn new(num: i64, den: i64) -> Self {
+        assert!(den != 0, "rational with zero denominator");
+        Self::checked_new(num, den).expect("rational out of range")
+    }
+
+    /// `num / den` in lowest terms, or `None` if `den` is zero or the result
+    /// does not fit in `i64`, e.g. `1 / i64::MIN`.
+    pub fn checked_new(num: i64, den: i64) -> Option<Self> {
+        Self::reduce(num.into(), den.into())
+    }
+
+    /// Lowest terms of a fraction worked out in `i128`, where no product or
+    /// sum of two `i64` fractions can overflow.
+    fn reduce(num: i128, den: i128) -> Option<Self> {
+        if den == 0 {
+            return None;
+        }
+        let sign = if den < 0 { -1 } else { 1 };
+        let g = gcd(num, den).max(1);
+        Some(Self {
+            num: i64::try_from(sign * num / g).ok()?,
+            den: i64::try_from(sign * den / g).ok()?,
+        })
+    }
+
+    pub fn checked_add(self, rhs: Rational) -> Option<Rational> {
+        let (a, b, c, d) = self.wide(rhs);
+        Self::reduce(a * d + c * b, b * d)
+    }
+
+    pub fn checked_sub(self, rhs: Rational) -> Option<Rational> {
+        let (a, b, c, d) = self.wide(rhs);
+        Self::reduce(a * d - c * b, b * d)
+    }
+
+    pub fn checked_neg(self) -> Option<Rational> {
+        Some(Rational { num: self.num.checked_neg()?, den: self.den })
+    }
+
+    fn wide(self, rhs: Rational) -> (i128, i128, i128, i128) {
+        (self.num.into(), self.den.into(), rhs.num.into(), rhs.den.into())
+    }
+
+    pub const fn integer(n: i64) -> Self {
+        Self { num: n, den: 1 }
+    }
+
+    pub fn numer(self) -> i64 {
+        self.num
+    }
+
+    pub fn denom(self) -> i64 {
+        self.den
+    }
+
+    pub fn is_integer(self) -> bool {
+        self.den == 1
+    }
+
+    /// Largest integer not above this value, i.e. the lattice cell it falls in.
+    pub fn floor(self) -> i64 {
+        self.num.div_euclid(self.den)
+    }
+
+    pub fn to_f32(self) -> f32 {
+        self.num as f32 / self.den as f32
+    }
+}
+
+impl From<i32> for Rational {
+    fn from(n: i32) -> Self {
+        Rational::integer(n as i64)
+    }
+}
+
+// The operators panic where the result leaves `i64`, as integer
+// arithmetic does; the `checked_*` methods report it instead.
+impl Add for Rational {
+    type Output = Rational;
+
+    fn add(self, rhs: Rational) -> Rational {
+        self.checked_add(rhs).expect("rational overflow")
+    }
+}
+
+impl Sub for Rational {
+    type Output = Rational;
+
+    fn sub(self, rhs: Rational) -> Rational {
+        self.checked_sub(rhs).expect("rational overflow")
+    }
+}
+
+impl Neg for Rational {
+    type Output = Rational;
+
+    fn neg(self) -> Rational {
+        self.checked_neg().expect("rational overflow")
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.num as i128 * other.den as i128).cmp(&(other.num as i128 * self.den as i128))
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl FromStr for Rational {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let parse = |t: &str| {
+            t.trim()
+                .parse::<i64>()
+                .map_err(|e| format!("invalid rational {:?}: {}", s, e))
+        };
+
+        match s.split_once('/') {
+            None => Ok(Rational::integer(parse(s)?)),
+            Some((n, d)) => {
+                let den = parse(d)?;
+                if den == 0 {
+                    return Err(format!("invalid rational {:?}: zero denominator", s));
+                }
+                Rational::checked_new(parse(n)?, den)
+                    .ok_or_else(|| format!("invalid rational {:?}: out of range", s))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.den == 1 {
+            write!(f, "{}", self.num)
+        } else {
+            write!(f, "{}/{}", self.num, self.den)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_and_compares_exactly() {
+        assert_eq!(Rational::new(2, 4), Rational::new(-1, -2));
+        assert_eq!(Rational::new(1, 3) + Rational::new(1, 6), Rational::new(1, 2));
+        assert_eq!(Rational::new(1, 2) + Rational::new(1, 2), Rational::integer(1));
+        assert!(Rational::new(1, 3) < Rational::new(1, 2));
+        assert_eq!(Rational::new(-1, 2).floor(), -1);
+        assert_eq!(Rational::new(7, 3).floor(), 2);
+    }
+
+    #[test]
+    fn checked_arithmetic_reports_overflow() {
+        let max = Rational::integer(i64::MAX);
+        let min = Rational::integer(i64::MIN);
+        assert_eq!(max.checked_add(Rational::integer(1)), None);
+        assert_eq!(min.checked_sub(Rational::integer(1)), None);
+        assert_eq!(min.checked_neg(), None);
+        assert_eq!(min.checked_sub(min), Some(Rational::ZERO));
+
+        // Products past `i64` along the way are fine if the result fits.
+        let third = Rational::new(i64::MAX, 3);
+        assert_eq!(third.checked_add(-third), Some(Rational::ZERO));
+        assert_eq!(third.checked_add(third), None);
+    }
+
+    #[test]
+    fn parses_integers_and_fractions() {
+        let values: Vec<Rational> = serde_json::from_str(r#"[3, "1/2", "-4/6", " 5 "]"#).unwrap();
+        assert_eq!(
+            values,
+            [
+                Rational::integer(3),
+                Rational::new(1, 2),
+                Rational::new(-2, 3),
+                Rational::integer(5),
+            ]
+        );
+
+        assert!("1/0".parse::<Rational>().is_err());
+        assert_eq!(
+            "-9223372036854775808/3".parse::<Rational>(),
+            Ok(Rational::checked_new(i64::MIN, 3).unwrap())
+        );
+        assert_eq!(
+            "1/-9223372036854775808".parse::<Rational>().unwrap_err(),
+            "invalid rational \"1/-9223372036854775808\": out of range"
+        );
+        assert!(serde_json::from_str::<Rational>("0.5").is_err());
+        assert_eq!(Rational::new(-2, 3).to_string(), "-2/3");
+    }
+}
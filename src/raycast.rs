@@ -0,0 +1,170 @@
+use crate::units::UnitScale;
+use crate::world::{PartInstanceId, World, WorldCell};
+use glam::Vec3;
+
+/// A ray in lattice units, where cell `(x, y, z)` spans `[x, x + 1)` on
+/// each axis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub dir: Vec3,
+}
+
+impl Ray {
+    pub fn new(origin: Vec3, dir: Vec3) -> Self {
+        Self { origin, dir }
+    }
+
+    /// Converts a world-space ray (e.g. from camera picking) to lattice
+    /// units. Distances along the converted ray stay in world units.
+    pub fn from_world(origin: Vec3, dir: Vec3, scale: UnitScale) -> Self {
+        let s = Vec3::new(scale.xy, scale.xy, scale.z);
+        Self { origin: origin / s, dir: dir / s }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    pub instance: PartInstanceId,
+    pub cell: WorldCell,
+    /// Outward normal of the face the ray entered through, or zero when the
+    /// ray starts inside the cell.
+    pub normal: [i32; 3],
+    /// Ray parameter at the hit, i.e. distance in units of `dir`.
+    pub distance: f32,
+}
+
+/// Walks the cells along `ray` (3D-DDA) and returns the first occupied one
+/// within `max_distance`. The distance bounds the walk, so it must be
+/// finite.
+pub fn raycast(world: &World, ray: Ray, max_distance: f32) -> Option<RayHit> {
+    if ray.dir == Vec3::ZERO || !max_distance.is_finite() {
+        return None;
+    }
+
+    let o = ray.origin.to_array();
+    let d = ray.dir.to_array();
+
+    let mut cell = o.map(|v| v.floor() as i32);
+    let mut step = [0i32; 3];
+    let mut t_max = [f32::INFINITY; 3];
+    let mut t_delta = [f32::INFINITY; 3];
+
+    for axis in 0..3 {
+        if d[axis] > 0.0 {
+            step[axis] = 1;
+            t_max[axis] = (cell[axis] as f32 + 1.0 - o[axis]) / d[axis];
+            t_delta[axis] = 1.0 / d[axis];
+        } else if d[axis] < 0.0 {
+            step[axis] = -1;
+            t_max[axis] = (o[axis] - cell[axis] as f32) / -d[axis];
+            t_delta[axis] = -1.0 / d[axis];
+        }
+    }
+
+    let mut normal = [0i32; 3];
+    let mut t = 0.0_f32;
+
+    while t <= max_distance {
+        let at = WorldCell { x: cell[0], y: cell[1], z: cell[2] };
+        if let Some(instance) = world.instance_at(at) {
+            return Some(RayHit { instance, cell: at, normal, distance: t });
+        }
+
+        let axis = (0..3)
+            .min_by(|&a, &b| t_max[a].total_cmp(&t_max[b]))
+            .expect("three axes");
+
+        t = t_max[axis];
+        cell[axis] += step[axis];
+        t_max[axis] += t_delta[axis];
+        normal = [0; 3];
+        normal[axis] = -step[axis];
+    }
+
+    None
+}
+
+/// [`raycast`] for a world-space ray, e.g. an unprojected cursor.
+pub fn raycast_world(
+    world: &World,
+    origin: Vec3,
+    dir: Vec3,
+    scale: UnitScale,
+    max_distance: f32,
+) -> Option<RayHit> {
+    raycast(world, Ray::from_world(origin, dir, scale), max_distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader;
+    use crate::units::lego;
+
+    fn world_with_brick() -> (World, PartInstanceId) {
+        let part = loader::load_part_dir("assets/parts/lego/3001").unwrap();
+        let mut world = World::new();
+        let id = world.place_part(&part, WorldCell { x: 0, y: 0, z: 0 }).unwrap();
+        (world, id)
+    }
+
+    #[test]
+    fn hits_top_face_from_above() {
+        let (world, id) = world_with_brick();
+
+        let hit = raycast(
+            &world,
+            Ray::new(Vec3::new(1.5, 2.5, 10.0), Vec3::new(0.0, 0.0, -1.0)),
+            100.0,
+        )
+        .expect("expected hit");
+
+        assert_eq!(hit.instance, id);
+        assert_eq!(hit.cell, WorldCell { x: 1, y: 2, z: 2 });
+        assert_eq!(hit.normal, [0, 0, 1]);
+        assert!((hit.distance - 7.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn diagonal_ray_enters_side_face() {
+        let (world, _) = world_with_brick();
+
+        let hit = raycast(
+            &world,
+            Ray::new(Vec3::new(-2.0, 0.5, 3.5), Vec3::new(1.0, 0.0, -0.5)),
+            100.0,
+        )
+        .expect("expected hit");
+
+        assert_eq!(hit.cell, WorldCell { x: 0, y: 0, z: 2 });
+        assert_eq!(hit.normal, [-1, 0, 0]);
+    }
+
+    #[test]
+    fn misses_and_inside_start() {
+        let (world, _) = world_with_brick();
+
+        let away = Ray::new(Vec3::new(0.5, 0.5, 5.0), Vec3::Z);
+        assert!(raycast(&world, away, 50.0).is_none());
+
+        let short = Ray::new(Vec3::new(0.5, 0.5, 10.0), Vec3::NEG_Z);
+        assert!(raycast(&world, short, 2.0).is_none());
+
+        let inside = raycast(&world, Ray::new(Vec3::new(0.5, 0.5, 0.5), Vec3::X), 5.0).unwrap();
+        assert_eq!(inside.normal, [0, 0, 0]);
+        assert_eq!(inside.distance, 0.0);
+    }
+
+    #[test]
+    fn world_space_rays_use_unit_scale() {
+        let (world, _) = world_with_brick();
+
+        // One plate above the brick top, in millimetres.
+        let origin = Vec3::new(lego::STUD_MM * 0.5, lego::STUD_MM * 0.5, lego::PLATE_MM * 4.0);
+        let hit = raycast_world(&world, origin, Vec3::NEG_Z, lego::SCALE_MM, 100.0).unwrap();
+
+        assert_eq!(hit.cell, WorldCell { x: 0, y: 0, z: 2 });
+        assert!((hit.distance - lego::PLATE_MM).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,135 @@
+use crate::loader::Part;
+use crate::orientation::Orientation;
+use crate::world::{Footprint, PartInstanceId, World, WorldCell};
+use std::cmp::Reverse;
+use std::collections::HashSet;
+
+/// A valid placement of a part that mates with the target connector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Snap {
+    pub offset: WorldCell,
+    pub orientation: Orientation,
+    /// Connector pairs mated by this placement, the target included.
+    pub mates: usize,
+}
+
+/// Enumerates every placement of `part` where one of its connectors mates
+/// with the free connector `target` (instance, connector index) and the
+/// world accepts the footprint. Results are ranked by mated connectors, most first.
+pub fn find_snaps(
+    world: &World,
+    part: &Part,
+    target: (PartInstanceId, usize),
+    orientations: &[Orientation],
+) -> Result<Vec<Snap>, String> {
+    let (target_id, target_index) = target;
+    let goal = world
+        .connector(target_id, target_index)
+        .ok_or_else(|| format!("no connector {} on instance {:?}", target_index, target_id))?;
+
+    if world.connections().is_connector_mated(target_id, target_index) {
+        return Err(format!(
+            "connector {} on instance {:?} is already mated",
+            target_index, target_id
+        ));
+    }
+
+    let origin = WorldCell { x: 0, y: 0, z: 0 };
+    let mut seen = HashSet::new();
+    let mut snaps = Vec::new();
+
+    for &orientation in orientations {
+        let local = Footprint::new(part, origin, orientation);
+
+        for c in &local.connectors {
+            if !c.fits(&goal) {
+                continue;
+            }
+
+            // Parts only move by whole cells, so a fractional gap can never
+            // be closed.
+            let dx = goal.pos.x - c.pos.x;
+            let dy = goal.pos.y - c.pos.y;
+            let dz = goal.pos.z - c.pos.z;
+            if !(dx.is_integer() && dy.is_integer() && dz.is_integer()) {
+                continue;
+            }
+            let offset = WorldCell {
+                x: dx.floor() as i32,
+                y: dy.floor() as i32,
+                z: dz.floor() as i32,
+            };
+            if !seen.insert((offset, orientation)) {
+                continue;
+            }
+
+            let footprint = local.translated(offset);
+            if world.check_footprint(&footprint).is_err() {
+                continue;
+            }
+
+            snaps.push(Snap {
+                offset,
+                orientation,
+                mates: world.mates_for(&footprint).len(),
+            });
+        }
+    }
+
+    snaps.sort_by_key(|s| {
+        (
+            Reverse(s.mates),
+            s.orientation,
+            (s.offset.z, s.offset.y, s.offset.x),
+        )
+    });
+
+    Ok(snaps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader;
+
+    #[test]
+    fn snaps_are_ranked_by_mates() {
+        let base = loader::load_part_dir("assets/parts/lego/3001").unwrap();
+        let brick = loader::load_part_dir("assets/parts/lego/3004").unwrap();
+        let mut world = World::new();
+        let id = world.place_part(&base, WorldCell { x: 0, y: 0, z: 0 }).unwrap();
+
+        // Stud 0 sits on the corner cell, so a 1x2 brick can cover it along
+        // either axis, fully on the base or hanging off the edge.
+        let snaps = find_snaps(&world, &brick, (id, 0), &Orientation::ALL).unwrap();
+
+        assert_eq!(snaps.len(), 8);
+        assert_eq!(snaps.iter().filter(|s| s.mates == 2).count(), 4);
+        assert!(snaps.windows(2).all(|w| w[0].mates >= w[1].mates));
+        assert_eq!(
+            snaps[0],
+            Snap {
+                offset: WorldCell { x: 0, y: 0, z: 3 },
+                orientation: Orientation::Deg0,
+                mates: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn occupied_targets_are_rejected() {
+        let base = loader::load_part_dir("assets/parts/lego/3001").unwrap();
+        let brick = loader::load_part_dir("assets/parts/lego/3004").unwrap();
+        let mut world = World::new();
+        let id = world.place_part(&base, WorldCell { x: 0, y: 0, z: 0 }).unwrap();
+        world.place_part(&brick, WorldCell { x: 0, y: 0, z: 3 }).unwrap();
+
+        assert!(find_snaps(&world, &brick, (id, 0), &Orientation::ALL).is_err());
+
+        // Stud 2 is free, but reaching it with the second tube would overlap
+        // the brick already sitting on studs 0 and 1.
+        let snaps = find_snaps(&world, &brick, (id, 2), &[Orientation::Deg0]).unwrap();
+        assert_eq!(snaps.len(), 1);
+        assert_eq!(snaps[0].offset, WorldCell { x: 0, y: 2, z: 3 });
+    }
+}
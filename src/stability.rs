@@ -0,0 +1,332 @@
+use crate::catalog::Catalog;
+use crate::connectors::ConnectorType;
+use crate::support::rests_on_ground;
+use crate::world::{PartInstanceId, World};
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+/// Clutch capacity per connector kind, in grams-force a single mated pair
+/// holds before pulling apart, plus the mass assumed for parts whose
+/// metadata has none.
+#[derive(Debug, Clone)]
+pub struct ClutchTable {
+    capacities: HashMap<ConnectorType, f32>,
+    pub default_mass_g: f32,
+}
+
+impl Default for ClutchTable {
+    /// Rough figures for ABS bricks; calibrate for real parts.
+    fn default() -> Self {
+        use ConnectorType::*;
+        let capacities = [
+            (Stud, 100.0),
+            (Tube, 100.0),
+            (Pin, 150.0),
+            (PinHole, 150.0),
+            (Axle, 150.0),
+            (AxleHole, 150.0),
+            (Clip, 60.0),
+            (Bar, 60.0),
+            (Ball, 80.0),
+            (Socket, 80.0),
+            (Hinge, 60.0),
+        ];
+        Self {
+            capacities: capacities.into_iter().collect(),
+            default_mass_g: 1.0,
+        }
+    }
+}
+
+impl ClutchTable {
+    pub fn capacity(&self, kind: ConnectorType) -> f32 {
+        self.capacities.get(&kind).copied().unwrap_or(0.0)
+    }
+
+    pub fn set_capacity(&mut self, kind: ConnectorType, grams: f32) {
+        self.capacities.insert(kind, grams);
+    }
+
+    /// A mated pair is as strong as its weaker side.
+    pub fn pair_capacity(&self, a: ConnectorType, b: ConnectorType) -> f32 {
+        self.capacity(a).min(self.capacity(b))
+    }
+}
+
+/// Estimated load on the connection through which `instance` hangs on
+/// `support`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConnectionLoad {
+    pub instance: PartInstanceId,
+    pub support: PartInstanceId,
+    /// Weight carried plus the pull its lever arm adds, in grams-force.
+    pub load: f32,
+    /// Summed clutch of the mated pairs, in grams-force.
+    pub capacity: f32,
+}
+
+impl ConnectionLoad {
+    pub fn utilization(&self) -> f32 {
+        if self.capacity > 0.0 {
+            self.load / self.capacity
+        } else {
+            f32::INFINITY
+        }
+    }
+
+    pub fn is_overloaded(&self) -> bool {
+        self.load > self.capacity
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StabilityReport {
+    /// One entry per loaded connection, ordered by `(instance, support)`.
+    pub loads: Vec<ConnectionLoad>,
+}
+
+impl StabilityReport {
+    pub fn overloaded(&self) -> impl Iterator<Item = &ConnectionLoad> + '_ {
+        self.loads.iter().filter(|l| l.is_overloaded())
+    }
+
+    pub fn is_stable(&self) -> bool {
+        self.overloaded().next().is_none()
+    }
+}
+
+/// Weight gathered on a part, with its first moments for the centre of mass.
+#[derive(Debug, Clone, Copy, Default)]
+struct Load {
+    weight: f32,
+    mx: f32,
+    my: f32,
+}
+
+impl Load {
+    fn centre(&self) -> (f32, f32) {
+        (self.mx / self.weight, self.my / self.weight)
+    }
+
+    fn scaled(&self, k: f32) -> Load {
+        Load { weight: self.weight * k, mx: self.mx * k, my: self.my * k }
+    }
+
+    fn add(&mut self, other: Load) {
+        self.weight += other.weight;
+        self.mx += other.mx;
+        self.my += other.my;
+    }
+}
+
+/// Simplified static analysis under gravity along -z.
+///
+/// Parts resting on the ground carry their loads into it. Every other part
+/// hangs on the neighbours one connection step closer to the ground, with
+/// its own weight and everything hanging on it split evenly between them.
+/// A connection's load is that share plus `share * lever / arm`, where the
+/// lever is the horizontal distance from the share's centre of mass to the
+/// centre of the mated connectors and the arm is how far the connectors
+/// spread from that centre (half a stud at least). Parts that do not reach
+/// the ground are left out; see `support::analyze_support`. A part without
+/// positive mass, from its metadata or the table's default, is an error:
+/// it would have no centre of mass.
+pub fn analyze_stability(
+    world: &World,
+    catalog: &Catalog,
+    table: &ClutchTable,
+) -> Result<StabilityReport, String> {
+    let graph = world.connections();
+
+    let mut depth: BTreeMap<PartInstanceId, usize> = BTreeMap::new();
+    let mut queue = VecDeque::new();
+    for (id, _) in world.instances() {
+        if rests_on_ground(world, id) {
+            depth.insert(id, 0);
+            queue.push_back(id);
+        }
+    }
+    while let Some(id) = queue.pop_front() {
+        let d = depth[&id];
+        for next in graph.neighbors(id) {
+            if let Entry::Vacant(e) = depth.entry(next) {
+                e.insert(d + 1);
+                queue.push_back(next);
+            }
+        }
+    }
+
+    let mut loads: BTreeMap<PartInstanceId, Load> = BTreeMap::new();
+    for &id in depth.keys() {
+        let instance = world.instance(id).expect("graph ids are placed");
+        let part = catalog
+            .get(&instance.part)
+            .ok_or_else(|| format!("missing part: {}", instance.part))?;
+        let mass = part.meta.mass_g.unwrap_or(table.default_mass_g);
+        if !(mass.is_finite() && mass > 0.0) {
+            return Err(format!("{} has no positive mass: {}", instance.part, mass));
+        }
+
+        let cells = world.instance_cells(id).unwrap_or(&[]);
+        let n = cells.len().max(1) as f32;
+        let cx = cells.iter().map(|c| c.x as f32 + 0.5).sum::<f32>() / n;
+        let cy = cells.iter().map(|c| c.y as f32 + 0.5).sum::<f32>() / n;
+        loads.insert(id, Load { weight: mass, mx: mass * cx, my: mass * cy });
+    }
+
+    let mut order: Vec<_> = depth.iter().filter(|(_, &d)| d > 0).collect();
+    order.sort_by(|a, b| b.1.cmp(a.1).then(b.0.cmp(a.0)));
+
+    let mut report = StabilityReport::default();
+
+    for (&id, &d) in order {
+        let supports: Vec<_> = graph
+            .neighbors(id)
+            .into_iter()
+            .filter(|n| depth.get(n) == Some(&(d - 1)))
+            .collect();
+        let share = loads[&id].scaled(1.0 / supports.len() as f32);
+
+        for support in supports {
+            let mut capacity = 0.0;
+            let mut points = Vec::new();
+            for mate in graph.mates(id, support) {
+                let (Some(local), Some(remote)) =
+                    (world.connector(id, mate.local), world.connector(support, mate.remote))
+                else {
+                    continue;
+                };
+                capacity += table.pair_capacity(local.kind, remote.kind);
+                points.push((local.pos.x.to_f32() + 0.5, local.pos.y.to_f32() + 0.5));
+            }
+
+            let n = points.len().max(1) as f32;
+            let jx = points.iter().map(|p| p.0).sum::<f32>() / n;
+            let jy = points.iter().map(|p| p.1).sum::<f32>() / n;
+            let spread = points
+                .iter()
+                .map(|p| (p.0 - jx).hypot(p.1 - jy))
+                .fold(0.0, f32::max);
+            let arm = spread + 0.5;
+
+            let (cx, cy) = share.centre();
+            let lever = (cx - jx).hypot(cy - jy);
+
+            report.loads.push(ConnectionLoad {
+                instance: id,
+                support,
+                load: share.weight * (1.0 + lever / arm),
+                capacity,
+            });
+            loads.get_mut(&support).expect("supports have depth").add(share);
+        }
+    }
+
+    report.loads.sort_by_key(|l| (l.instance, l.support));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::WorldCell;
+
+    fn catalog() -> Catalog {
+        Catalog::load_dir("assets/parts").unwrap()
+    }
+
+    #[test]
+    fn flags_a_long_cantilever() {
+        let catalog = catalog();
+        let brick = catalog.get("lego:3001").unwrap();
+        let block = catalog.get("lego:3003").unwrap();
+        let mut world = World::new();
+
+        let base = world.place_part(brick, WorldCell { x: 0, y: 0, z: 0 }).unwrap();
+        let cap = world.place_part(block, WorldCell { x: 0, y: 0, z: 3 }).unwrap();
+        // Held by the last row of studs only, reaching three studs out.
+        let arm = world.place_part(brick, WorldCell { x: 0, y: 3, z: 3 }).unwrap();
+        let tip = world.place_part(brick, WorldCell { x: 0, y: 6, z: 6 }).unwrap();
+
+        let mut table = ClutchTable::default();
+        table.set_capacity(ConnectorType::Stud, 4.0);
+        table.set_capacity(ConnectorType::Tube, 4.0);
+
+        let report = analyze_stability(&world, &catalog, &table).unwrap();
+        assert_eq!(report.loads.len(), 3);
+
+        let load = |id| *report.loads.iter().find(|l| l.instance == id).unwrap();
+
+        // Centred on its support: no lever, just its own weight.
+        let centred = load(cap);
+        assert_eq!(centred.support, base);
+        assert!((centred.load - 1.17).abs() < 1e-4);
+        assert_eq!(centred.capacity, 16.0);
+        assert!(!centred.is_overloaded());
+
+        let root = load(arm);
+        assert_eq!(root.support, base);
+        assert_eq!(root.capacity, 8.0);
+        assert!(root.load > load(tip).load);
+        assert!(root.is_overloaded());
+
+        let flagged: Vec<_> = report.overloaded().map(|l| l.instance).collect();
+        assert!(flagged.contains(&arm));
+        assert!(!report.is_stable());
+
+        assert!(analyze_stability(&world, &catalog, &ClutchTable::default())
+            .unwrap()
+            .is_stable());
+    }
+
+    #[test]
+    fn rejects_massless_parts() {
+        let mut catalog = catalog();
+        let mut ghost = crate::loader::load_part_dir("assets/parts/lego/3003").unwrap();
+        ghost.meta.id = "test:ghost".to_string();
+        ghost.meta.mass_g = Some(0.0);
+        assert_eq!(ghost.meta.validate().unwrap_err(), "mass_g must be positive, not 0");
+        catalog.insert(ghost).unwrap();
+        let mut bare = crate::loader::load_part_dir("assets/parts/lego/3003").unwrap();
+        bare.meta.id = "test:bare".to_string();
+        bare.meta.mass_g = None;
+        catalog.insert(bare).unwrap();
+
+        let check = |part: &str, table: &ClutchTable| {
+            let mut world = World::new();
+            let at = |z| WorldCell { x: 0, y: 0, z };
+            world.place_part(catalog.get("lego:3001").unwrap(), at(0)).unwrap();
+            world.place_part(catalog.get(part).unwrap(), at(3)).unwrap();
+            analyze_stability(&world, &catalog, table)
+        };
+
+        let err = check("test:ghost", &ClutchTable::default()).unwrap_err();
+        assert_eq!(err, "test:ghost has no positive mass: 0");
+
+        let mut table = ClutchTable::default();
+        assert!(check("test:bare", &table).is_ok());
+        table.default_mass_g = 0.0;
+        assert_eq!(check("test:bare", &table).unwrap_err(), "test:bare has no positive mass: 0");
+    }
+
+    #[test]
+    fn splits_load_between_supports() {
+        let catalog = catalog();
+        let block = catalog.get("lego:3003").unwrap();
+        let brick = catalog.get("lego:3001").unwrap();
+        let mut world = World::new();
+
+        let left = world.place_part(block, WorldCell { x: 0, y: 0, z: 0 }).unwrap();
+        let right = world.place_part(block, WorldCell { x: 0, y: 2, z: 0 }).unwrap();
+        let bridge = world.place_part(brick, WorldCell { x: 0, y: 0, z: 3 }).unwrap();
+
+        let report = analyze_stability(&world, &catalog, &ClutchTable::default()).unwrap();
+        let shares: Vec<_> = report.loads.iter().map(|l| (l.instance, l.support)).collect();
+        assert_eq!(shares, vec![(bridge, left), (bridge, right)]);
+
+        // Half the weight, one stud off each support's centre.
+        for l in &report.loads {
+            assert!((l.load - 1.16 * (1.0 + 1.0 / (0.5f32.hypot(0.5) + 0.5))).abs() < 1e-4);
+        }
+    }
+}
@@ -0,0 +1,300 @@
+//! Named sub-assemblies placed as units. A placed copy resolves to
+//! ordinary part instances that share a [`SubmodelInstanceId`], so every
+//! exporter sees plain parts. Nesting is one level deep: capturing a world
+//! that holds placed submodels with [`Submodel::from_world`] flattens them
+//! into loose parts of the new submodel.
+
+use crate::catalog::Catalog;
+use crate::lattice::Cell;
+use crate::loader::Part;
+use crate::orientation::Orientation;
+use crate::world::{cell_bounds, min_corner, Footprint, World, WorldCell};
+use serde::{Deserialize, Serialize};
+
+/// Identifies one placed copy of a submodel within a world.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct SubmodelInstanceId(pub u64);
+
+/// Marks a part instance as belonging to a placed submodel.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubmodelRef {
+    pub id: SubmodelInstanceId,
+    pub name: String,
+}
+
+/// One part of a submodel, positioned in the submodel's own cells.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubmodelPart {
+    pub part: String,
+    pub offset: WorldCell,
+    #[serde(default)]
+    pub orientation: Orientation,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+}
+
+/// A named, reusable sub-assembly such as a window or a wall section.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Submodel {
+    pub name: String,
+    pub parts: Vec<SubmodelPart>,
+}
+
+/// A submodel part resolved against a catalog and a placement.
+#[derive(Clone, Copy)]
+pub struct ResolvedPart<'a> {
+    pub part: &'a Part,
+    pub offset: WorldCell,
+    pub orientation: Orientation,
+    pub color: Option<&'a str>,
+}
+
+impl Submodel {
+    /// Captures every instance of `world` as it stands, e.g. a sub-assembly
+    /// built on its own.
+    pub fn from_world(name: &str, world: &World) -> Self {
+        let parts = world
+            .instances()
+            .map(|(_, inst)| SubmodelPart {
+                part: inst.part.clone(),
+                offset: inst.offset,
+                orientation: inst.orientation,
+                color: inst.color.clone(),
+            })
+            .collect();
+
+        Self { name: name.to_string(), parts }
+    }
+
+    /// Where each part lands when the whole submodel is placed at `offset`
+    /// with `orientation`. Like a single part, the submodel turns within its
+    /// bounding box, so its lowest corner stays at `offset` plus wherever
+    /// its own cells start.
+    pub fn resolve<'a>(
+        &'a self,
+        catalog: &'a Catalog,
+        offset: WorldCell,
+        orientation: Orientation,
+    ) -> Result<Vec<ResolvedPart<'a>>, String> {
+        catalog.require_all(self.parts.iter().map(|p| p.part.as_str()))?;
+
+        let local: Vec<_> = self
+            .parts
+            .iter()
+            .map(|p| {
+                let part = catalog.get(&p.part).expect("checked above");
+                (p, part, Footprint::new(part, p.offset, p.orientation).cells)
+            })
+            .collect();
+
+        let Some(bounds) = cell_bounds(local.iter().flat_map(|(_, _, cells)| cells.iter())) else {
+            return Ok(Vec::new());
+        };
+
+        Ok(local
+            .into_iter()
+            .map(|(p, part, cells)| {
+                let turned = cells.iter().map(|&c| {
+                    let c = orientation.apply(Cell { x: c.x, y: c.y, z: c.z }, &bounds);
+                    WorldCell::from(c).offset(offset)
+                });
+                let want = min_corner(turned);
+
+                let composed = Orientation::from_quarter_turns(
+                    orientation.quarter_turns() + p.orientation.quarter_turns(),
+                );
+                let at_origin = Footprint::new(part, WorldCell { x: 0, y: 0, z: 0 }, composed);
+                let have = min_corner(at_origin.cells.iter().copied());
+
+                ResolvedPart {
+                    part,
+                    offset: WorldCell {
+                        x: want.x - have.x,
+                        y: want.y - have.y,
+                        z: want.z - have.z,
+                    },
+                    orientation: composed,
+                    color: p.color.as_deref(),
+                }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembly::plan_assembly;
+    use crate::bom::BillOfMaterials;
+    use crate::instructions::group_steps;
+    use crate::lattice::Bounds;
+    use crate::model::ModelFile;
+    use std::collections::HashSet;
+
+    fn at(x: i32, y: i32, z: i32) -> WorldCell {
+        WorldCell { x, y, z }
+    }
+
+    /// A 2x4 with a red 1x2 on its far end.
+    fn pillar(catalog: &Catalog) -> Submodel {
+        let mut world = World::new();
+        world.place_part(catalog.get("lego:3001").unwrap(), at(0, 0, 0)).unwrap();
+        let cap = world.place_part(catalog.get("lego:3004").unwrap(), at(0, 2, 3)).unwrap();
+        world.set_color(cap, Some("red".to_string())).unwrap();
+        Submodel::from_world("pillar", &world)
+    }
+
+    fn put(world: &mut World, catalog: &Catalog, name: &str, at: WorldCell) -> SubmodelInstanceId {
+        world.place_submodel(name, catalog, at, Orientation::Deg0).unwrap()
+    }
+
+    fn cells_of(world: &World, ids: &[crate::world::PartInstanceId]) -> HashSet<WorldCell> {
+        ids.iter()
+            .flat_map(|id| world.instance_cells(*id).unwrap().iter().copied())
+            .collect()
+    }
+
+    #[test]
+    fn turns_as_one_unit() {
+        let catalog = Catalog::load_dir("assets/parts").unwrap();
+        let sub = pillar(&catalog);
+
+        let mut flat = World::new();
+        flat.define_submodel(sub.clone()).unwrap();
+        let a = put(&mut flat, &catalog, "pillar", at(0, 0, 0));
+        let source = cells_of(&flat, &flat.submodel_parts(a));
+
+        let mut world = World::new();
+        world.define_submodel(sub).unwrap();
+        let id = world
+            .place_submodel("pillar", &catalog, at(10, 10, 0), Orientation::Deg90)
+            .unwrap();
+        let parts = world.submodel_parts(id);
+        assert_eq!(parts.len(), 2);
+        assert!(world.connections().is_one_piece());
+        assert_eq!(world.instance(parts[1]).unwrap().orientation, Orientation::Deg90);
+        assert_eq!(world.instance(parts[1]).unwrap().color.as_deref(), Some("red"));
+
+        // The same shape, turned within its 2x4 box and moved to (10, 10).
+        let bounds = Bounds { x: [0, 1], y: [0, 3], z: [0, 5] };
+        let expected: HashSet<_> = source
+            .iter()
+            .map(|c| {
+                let t = Orientation::Deg90.apply(Cell { x: c.x, y: c.y, z: c.z }, &bounds);
+                WorldCell::from(t).offset(at(10, 10, 0))
+            })
+            .collect();
+        assert_eq!(cells_of(&world, &parts), expected);
+    }
+
+    #[test]
+    fn places_removes_and_undoes_whole() {
+        let catalog = Catalog::load_dir("assets/parts").unwrap();
+        let mut world = World::new();
+        world.define_submodel(pillar(&catalog)).unwrap();
+        assert!(world.define_submodel(pillar(&catalog)).is_err());
+
+        let first = put(&mut world, &catalog, "pillar", at(0, 0, 0));
+        // Only the cap collides, yet nothing of the second copy may land.
+        let blocker = world.place_part(catalog.get("lego:3004").unwrap(), at(4, 2, 3)).unwrap();
+        let err = world
+            .place_submodel("pillar", &catalog, at(4, 0, 0), Orientation::Deg0)
+            .unwrap_err();
+        assert!(err.starts_with("submodel pillar part 1:"), "{}", err);
+        assert_eq!(world.instance_count(), 3);
+        world.remove_part(blocker).unwrap();
+
+        let second = put(&mut world, &catalog, "pillar", at(4, 0, 0));
+        assert_ne!(first, second);
+        assert_eq!(world.submodel_instances().len(), 2);
+
+        let bom = BillOfMaterials::from_world(&world, &catalog).unwrap();
+        assert_eq!(bom.total(), 4);
+
+        world.remove_submodel(first).unwrap();
+        assert_eq!(world.instance_count(), 2);
+        world.undo().unwrap();
+        assert_eq!(world.submodel_parts(first).len(), 2);
+        world.undo().unwrap();
+        assert!(world.submodel_parts(second).is_empty());
+        assert_eq!(world.instance_count(), 2);
+    }
+
+    #[test]
+    fn moves_as_one_unit() {
+        let catalog = Catalog::load_dir("assets/parts").unwrap();
+        let mut world = World::new();
+        world.define_submodel(pillar(&catalog)).unwrap();
+        let unit = put(&mut world, &catalog, "pillar", at(0, 0, 0));
+        let parts = world.submodel_parts(unit);
+        let before = cells_of(&world, &parts);
+
+        // One stud along y, partly into cells the unit itself leaves.
+        world.move_submodel(unit, at(0, 1, 0)).unwrap();
+        let shifted: HashSet<_> = before.iter().map(|c| c.offset(at(0, 1, 0))).collect();
+        assert_eq!(cells_of(&world, &parts), shifted);
+        assert_eq!(world.instance(parts[1]).unwrap().color.as_deref(), Some("red"));
+        assert!(world.connections().is_one_piece());
+
+        world.place_part(catalog.get("lego:3004").unwrap(), at(0, 6, 3)).unwrap();
+        let err = world.move_submodel(unit, at(0, 3, 0)).unwrap_err();
+        assert!(err.contains("occupied"), "{}", err);
+        assert_eq!(cells_of(&world, &parts), shifted);
+        assert!(world.move_submodel(SubmodelInstanceId(99), at(0, 1, 0)).is_err());
+
+        world.undo().unwrap();
+        world.undo().unwrap();
+        assert_eq!(cells_of(&world, &parts), before);
+        assert_eq!(world.instance_count(), 2);
+    }
+
+    #[test]
+    fn never_reuses_copy_ids() {
+        let catalog = Catalog::load_dir("assets/parts").unwrap();
+        let mut world = World::new();
+        world.define_submodel(pillar(&catalog)).unwrap();
+        world.define_submodel(Submodel { name: "empty".to_string(), parts: Vec::new() }).unwrap();
+
+        let first = put(&mut world, &catalog, "pillar", at(0, 0, 0));
+        let newest = put(&mut world, &catalog, "pillar", at(4, 0, 0));
+        world.remove_submodel(newest).unwrap();
+        let next = put(&mut world, &catalog, "pillar", at(8, 0, 0));
+        assert_ne!(next, newest);
+
+        world.undo().unwrap();
+        world.undo().unwrap();
+        assert_eq!(world.submodel_parts(newest).len(), 2);
+        assert_eq!(world.submodel_instances().len(), 2);
+
+        let a = put(&mut world, &catalog, "empty", at(0, 0, 0));
+        let b = put(&mut world, &catalog, "empty", at(0, 0, 0));
+        assert_ne!(a, b);
+
+        // Saving keeps the counter even though the newest copies are gone.
+        world.remove_submodel(newest).unwrap();
+        let model = ModelFile::from_world(&world);
+        let mut loaded = model.to_world(&catalog).unwrap();
+        let after = put(&mut loaded, &catalog, "pillar", at(4, 0, 0));
+        assert!(after > b && after != first);
+    }
+
+    #[test]
+    fn survives_saving_and_keeps_its_own_steps() {
+        let catalog = Catalog::load_dir("assets/parts").unwrap();
+        let mut world = World::new();
+        world.define_submodel(pillar(&catalog)).unwrap();
+        let loose = world.place_part(catalog.get("lego:3001").unwrap(), at(0, 0, 0)).unwrap();
+        let unit = put(&mut world, &catalog, "pillar", at(2, 0, 0));
+
+        let plan = plan_assembly(&world);
+        let steps = group_steps(&world, &plan, 10);
+        assert_eq!(steps[0].added, vec![loose]);
+        assert_eq!(steps[1].added, vec![world.submodel_parts(unit)[0]]);
+
+        let model = ModelFile::from_world(&world);
+        let json = serde_json::to_string(&model).unwrap();
+        let loaded = serde_json::from_str::<ModelFile>(&json).unwrap().to_world(&catalog).unwrap();
+        assert_eq!(loaded.submodel("pillar"), world.submodel("pillar"));
+        assert_eq!(loaded.submodel_parts(unit), world.submodel_parts(unit));
+    }
+}
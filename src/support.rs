@@ -0,0 +1,111 @@
+use crate::world::{Ground, PartInstanceId, World};
+use std::collections::BTreeSet;
+
+/// Which instances reach the ground through a chain of connections.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SupportReport {
+    /// Instances resting on the ground, or connected to one that does.
+    pub grounded: BTreeSet<PartInstanceId>,
+    /// Unsupported instances that touch nothing at all.
+    pub floating: Vec<PartInstanceId>,
+    /// Unsupported groups of connected instances, each in id order.
+    pub islands: Vec<Vec<PartInstanceId>>,
+}
+
+impl SupportReport {
+    pub fn is_supported(&self) -> bool {
+        self.floating.is_empty() && self.islands.is_empty()
+    }
+
+    /// Every unsupported instance, in id order.
+    pub fn unsupported(&self) -> Vec<PartInstanceId> {
+        let mut all: Vec<_> = self
+            .floating
+            .iter()
+            .chain(self.islands.iter().flatten())
+            .copied()
+            .collect();
+        all.sort();
+        all
+    }
+}
+
+/// Checks every instance against the world's ground. An instance rests on
+/// the ground when one of its cells sits on the ground level over the build
+/// surface.
+pub fn analyze_support(world: &World) -> SupportReport {
+    let mut report = SupportReport::default();
+
+    for component in world.connections().connected_components() {
+        if component.iter().any(|&id| rests_on_ground(world, id)) {
+            report.grounded.extend(component);
+        } else if component.len() == 1 {
+            report.floating.push(component[0]);
+        } else {
+            report.islands.push(component);
+        }
+    }
+
+    report
+}
+
+/// Whether `id` itself sits on the world's ground (a plane at `z = 0` when
+/// none is set), rather than on other parts.
+pub fn rests_on_ground(world: &World, id: PartInstanceId) -> bool {
+    let ground = world.ground().copied().unwrap_or(Ground::Plane { z: 0 });
+    let level = ground.level();
+
+    world
+        .instance_cells(id)
+        .into_iter()
+        .flatten()
+        .any(|c| c.z == level && ground.supports(*c))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::loader;
+    use crate::world::WorldCell;
+
+    #[test]
+    fn finds_floating_parts_and_islands() {
+        let brick = loader::load_part_dir("assets/parts/lego/3001").unwrap();
+        let plate = loader::load_part_dir("assets/parts/lego/3003").unwrap();
+        let mut world = World::new();
+
+        let base = world.place_part(&brick, WorldCell { x: 0, y: 0, z: 0 }).unwrap();
+        let top = world.place_part(&plate, WorldCell { x: 0, y: 0, z: 3 }).unwrap();
+        let lone = world.place_part(&plate, WorldCell { x: 10, y: 0, z: 6 }).unwrap();
+        let low = world.place_part(&brick, WorldCell { x: 20, y: 0, z: 9 }).unwrap();
+        let high = world.place_part(&plate, WorldCell { x: 20, y: 0, z: 12 }).unwrap();
+
+        let report = analyze_support(&world);
+
+        assert_eq!(report.grounded, BTreeSet::from([base, top]));
+        assert_eq!(report.floating, vec![lone]);
+        assert_eq!(report.islands, vec![vec![low, high]]);
+        assert_eq!(report.unsupported(), vec![lone, low, high]);
+        assert!(!report.is_supported());
+
+        world.remove_part(lone).unwrap();
+        world.remove_part(high).unwrap();
+        world.remove_part(low).unwrap();
+        assert!(analyze_support(&world).is_supported());
+    }
+
+    #[test]
+    fn uses_the_world_ground() {
+        let brick = loader::load_part_dir("assets/parts/lego/3003").unwrap();
+        let mut world = World::new();
+        let raised = world.place_part(&brick, WorldCell { x: 0, y: 0, z: 3 }).unwrap();
+
+        assert_eq!(analyze_support(&world).floating, vec![raised]);
+
+        world.set_ground(Some(Ground::Baseplate { z: 3, x: [0, 1], y: [0, 1] }));
+        assert!(analyze_support(&world).is_supported());
+
+        world.set_ground(Some(Ground::Baseplate { z: 3, x: [4, 9], y: [0, 9] }));
+        assert!(!analyze_support(&world).is_supported());
+    }
+}
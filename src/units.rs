@@ -1,4 +1,3 @@
-#![allow(dead_code)]
 #[derive(Clone, Copy, Debug)]
 pub struct UnitScale {
     pub xy: f32,
@@ -99,36 +99,34 @@ impl ApplicationHandler for App {
                 }
             }
 
-            WindowEvent::MouseInput { state, button, .. } => {
-                if button == MouseButton::Left {
-                    if state == ElementState::Pressed {
-                        let now = Instant::now();
-                        let is_double = self
-                            .last_click
-                            .map(|t| now.duration_since(t) < Duration::from_millis(300))
-                            .unwrap_or(false);
-
-                        self.last_click = Some(now);
-
-                        if is_double {
-                            if let (Some(r), Some((x, y))) =
-                                (self.renderer.as_mut(), self.last_cursor)
-                            {
-                                if let Some(p) = r.pick_focus_point(x, y) {
-                                    r.set_focus(p);
-                                    if let Some(w) = self.window {
-                                        w.request_redraw();
-                                    }
+            WindowEvent::MouseInput { state, button: MouseButton::Left, .. } => {
+                if state == ElementState::Pressed {
+                    let now = Instant::now();
+                    let is_double = self
+                        .last_click
+                        .map(|t| now.duration_since(t) < Duration::from_millis(300))
+                        .unwrap_or(false);
+
+                    self.last_click = Some(now);
+
+                    if is_double {
+                        if let (Some(r), Some((x, y))) =
+                            (self.renderer.as_mut(), self.last_cursor)
+                        {
+                            if let Some(p) = r.pick_focus_point(x, y) {
+                                r.set_focus(p);
+                                if let Some(w) = self.window {
+                                    w.request_redraw();
                                 }
                             }
                         }
-
-                        self.dragging = true;
-                        self.last_cursor = None;
-                    } else {
-                        self.dragging = false;
-                        self.last_cursor = None;
                     }
+
+                    self.dragging = true;
+                    self.last_cursor = None;
+                } else {
+                    self.dragging = false;
+                    self.last_cursor = None;
                 }
             }
 
@@ -168,15 +166,13 @@ impl ApplicationHandler for App {
                 }
             }
 
-            WindowEvent::PinchGesture { delta, .. } => {
-                if delta.is_finite() {
-                    if let Some(r) = self.renderer.as_mut() {
-                        r.on_camera_scroll(delta as f32 * 8.0);
-                    }
+            WindowEvent::PinchGesture { delta, .. } if delta.is_finite() => {
+                if let Some(r) = self.renderer.as_mut() {
+                    r.on_camera_scroll(delta as f32 * 8.0);
+                }
 
-                    if let Some(w) = self.window {
-                        w.request_redraw();
-                    }
+                if let Some(w) = self.window {
+                    w.request_redraw();
                 }
             }
 
@@ -1,4 +1,4 @@
-use crate::visuals::units::UnitScale;
+use crate::units::UnitScale;
 
 #[derive(Clone, Copy, Debug)]
 pub struct GridSize {
@@ -3,7 +3,9 @@ mod renderer;
 mod camera;
 mod camera_controller;
 mod grid;
-mod units;
+mod offscreen;
+
+pub use offscreen::{OffscreenRenderer, Solid};
 
 pub fn run() -> anyhow::Result<()> {
     app::run()
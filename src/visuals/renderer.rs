@@ -8,7 +8,7 @@ use winit::window::Window;
 use crate::visuals::camera::Camera;
 use crate::visuals::camera_controller::CameraController;
 use crate::visuals::grid::{self, GridSize, LineVertex, Wall};
-use crate::visuals::units::lego;
+use crate::units::lego;
 
 #[repr(C)]
 #[derive(Clone, Copy, Pod, Zeroable)]
@@ -407,6 +407,7 @@ impl Renderer {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn intersect_walls(
     origin: Vec3,
     dir: Vec3,
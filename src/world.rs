@@ -1,8 +1,20 @@
-use crate::lattice::Cell;
-use std::collections::HashMap;
+use crate::catalog::Catalog;
+use crate::chunk::ChunkMap;
+use crate::connectors::{ConnectorType, Position};
+use crate::graph::ConnectionGraph;
+use crate::history::{Command, History};
+use crate::lattice::{Bounds, Cell};
+use crate::orientation::Orientation;
+use crate::placement::{PlacementReport, ProtrusionConflict};
+use crate::rational::Rational;
+use crate::submodel::{Submodel, SubmodelInstanceId, SubmodelRef};
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::mpsc::{channel, Receiver, Sender};
 use crate::loader::Part;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct WorldCell {
     pub x: i32,
     pub y: i32,
@@ -15,56 +27,1007 @@ impl From<Cell> for WorldCell {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+impl WorldCell {
+    pub fn offset(self, by: WorldCell) -> WorldCell {
+        WorldCell {
+            x: self.x + by.x,
+            y: self.y + by.y,
+            z: self.z + by.z,
+        }
+    }
+
+    /// Like [`offset`](Self::offset), but `None` if a coordinate overflows.
+    pub fn checked_offset(self, by: WorldCell) -> Option<WorldCell> {
+        Some(WorldCell {
+            x: self.x.checked_add(by.x)?,
+            y: self.y.checked_add(by.y)?,
+            z: self.z.checked_add(by.z)?,
+        })
+    }
+
+    /// The cell a connector position falls in.
+    pub fn containing(pos: Position) -> WorldCell {
+        WorldCell {
+            x: pos.x.floor() as i32,
+            y: pos.y.floor() as i32,
+            z: pos.z.floor() as i32,
+        }
+    }
+
+    /// Moves a connector position by this many cells.
+    pub fn offset_pos(self, pos: Position) -> Position {
+        Position {
+            x: pos.x + Rational::from(self.x),
+            y: pos.y + Rational::from(self.y),
+            z: pos.z + Rational::from(self.z),
+        }
+    }
+}
+
+/// Smallest box holding every cell, or `None` for no cells.
+pub(crate) fn cell_bounds<'c>(cells: impl Iterator<Item = &'c WorldCell>) -> Option<Bounds> {
+    cells.fold(None, |acc: Option<Bounds>, c| {
+        Some(match acc {
+            None => Bounds { x: [c.x, c.x], y: [c.y, c.y], z: [c.z, c.z] },
+            Some(b) => Bounds {
+                x: [b.x[0].min(c.x), b.x[1].max(c.x)],
+                y: [b.y[0].min(c.y), b.y[1].max(c.y)],
+                z: [b.z[0].min(c.z), b.z[1].max(c.z)],
+            },
+        })
+    })
+}
+
+/// Lowest x, y and z over `cells`, taken axis by axis.
+pub(crate) fn min_corner(cells: impl Iterator<Item = WorldCell>) -> WorldCell {
+    cells.fold(
+        WorldCell { x: i32::MAX, y: i32::MAX, z: i32::MAX },
+        |m, c| WorldCell { x: m.x.min(c.x), y: m.y.min(c.y), z: m.z.min(c.z) },
+    )
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct PartInstanceId(pub u64);
 
+/// The build surface parts stand on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Ground {
+    /// An endless floor; no cell may go below `z`.
+    Plane { z: i32 },
+    /// A plate covering the inclusive stud ranges `x` and `y` with its top
+    /// at `z`. Cells must stay above it, i.e. no lower than `z` and within
+    /// its edges.
+    Baseplate { z: i32, x: [i32; 2], y: [i32; 2] },
+}
+
+impl Ground {
+    /// Lowest cell row parts may fill.
+    pub fn level(&self) -> i32 {
+        match *self {
+            Ground::Plane { z } | Ground::Baseplate { z, .. } => z,
+        }
+    }
+
+    pub fn supports(&self, cell: WorldCell) -> bool {
+        match *self {
+            Ground::Plane { z } => cell.z >= z,
+            Ground::Baseplate { z, x, y } => {
+                cell.z >= z
+                    && (x[0]..=x[1]).contains(&cell.x)
+                    && (y[0]..=y[1]).contains(&cell.y)
+            }
+        }
+    }
+}
+
+/// Largest angle, in radians, by which two mating connectors may miss
+/// facing each other exactly. Absorbs rounding in authored normals and
+/// quaternions.
+pub const MATE_ANGLE_TOLERANCE: f32 = 0.01;
+
+/// A connector of a part, positioned in world cells.
+#[derive(Debug, Clone, Copy)]
+pub struct WorldConnector {
+    pub index: usize,
+    pub kind: ConnectorType,
+    pub pos: Position,
+    /// Unit vector the connector faces, in world axes.
+    pub normal: Vec3,
+}
+
+impl WorldConnector {
+    /// Whether the two connectors could mate if they shared a position.
+    pub fn fits(&self, other: &WorldConnector) -> bool {
+        self.kind.mates_with(other.kind)
+            && self.normal.dot(other.normal) <= -MATE_ANGLE_TOLERANCE.cos()
+    }
+
+    pub fn mates(&self, other: &WorldConnector) -> bool {
+        self.pos == other.pos && self.fits(other)
+    }
+}
+
+/// Cells and connectors a part covers at a given offset and orientation.
+#[derive(Debug, Clone)]
+pub struct Footprint {
+    pub cells: Vec<WorldCell>,
+    pub connectors: Vec<WorldConnector>,
+    /// Cells outside the part that its protruding connectors reach into,
+    /// as `(connector index, cell)`.
+    pub protrusions: Vec<(usize, WorldCell)>,
+}
+
+impl Footprint {
+    pub fn new(part: &Part, offset: WorldCell, orientation: Orientation) -> Self {
+        let bounds = &part.lattice.bounds;
+
+        let cells: Vec<WorldCell> = part
+            .lattice
+            .occupied
+            .iter()
+            .map(|c| WorldCell::from(orientation.apply(*c, bounds)).offset(offset))
+            .collect();
+
+        let connectors: Vec<WorldConnector> = part
+            .connectors
+            .connectors
+            .iter()
+            .enumerate()
+            .map(|(index, c)| WorldConnector {
+                index,
+                kind: c.kind,
+                pos: offset.offset_pos(orientation.apply_pos(c.pos, bounds)),
+                normal: orientation.rotation() * c.dir.normal(),
+            })
+            .collect();
+
+        let own: HashSet<WorldCell> = cells.iter().copied().collect();
+        let protrusions = connectors
+            .iter()
+            .filter(|c| c.kind.protrudes())
+            .map(|c| (c.index, WorldCell::containing(c.pos)))
+            .filter(|(_, cell)| !own.contains(cell))
+            .collect();
+
+        Self { cells, connectors, protrusions }
+    }
+
+    pub fn translated(&self, by: WorldCell) -> Self {
+        Self {
+            cells: self.cells.iter().map(|c| c.offset(by)).collect(),
+            connectors: self
+                .connectors
+                .iter()
+                .map(|c| WorldConnector { pos: by.offset_pos(c.pos), ..*c })
+                .collect(),
+            protrusions: self
+                .protrusions
+                .iter()
+                .map(|&(index, cell)| (index, cell.offset(by)))
+                .collect(),
+        }
+    }
+}
+
+/// What a placed instance is: which part, where, and how it is dressed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartInstance {
+    /// Catalog id, e.g. `lego:3001`.
+    pub part: String,
+    pub system: String,
+    pub offset: WorldCell,
+    pub orientation: Orientation,
+    /// `None` keeps the part's default material.
+    pub color: Option<String>,
+    pub label: Option<String>,
+    /// The placed submodel this part came in with, if any.
+    pub submodel: Option<SubmodelRef>,
+}
+
+/// One part to place as part of a batch.
+#[derive(Clone, Copy)]
+pub struct Placement<'a> {
+    pub part: &'a Part,
+    pub offset: WorldCell,
+    pub orientation: Orientation,
+}
+
+/// An instance together with the footprint it occupies.
+#[derive(Debug, Clone)]
+pub struct InstanceRecord {
+    pub instance: PartInstance,
+    pub footprint: Footprint,
+}
+
+impl InstanceRecord {
+    /// The record shifted to `offset`, or an error if that would take a
+    /// cell past the edge of the `i32` lattice.
+    fn moved_to(&self, offset: WorldCell) -> Result<InstanceRecord, String> {
+        let out_of_range = || format!("offset {:?} is out of range", offset);
+        let from = self.instance.offset;
+        let delta = match (
+            offset.x.checked_sub(from.x),
+            offset.y.checked_sub(from.y),
+            offset.z.checked_sub(from.z),
+        ) {
+            (Some(x), Some(y), Some(z)) => WorldCell { x, y, z },
+            _ => return Err(out_of_range()),
+        };
+
+        let protrusions = self.footprint.protrusions.iter().map(|(_, cell)| cell);
+        let mut cells = self.footprint.cells.iter().chain(protrusions);
+        if !cells.all(|cell| cell.checked_offset(delta).is_some()) {
+            return Err(out_of_range());
+        }
+
+        Ok(InstanceRecord {
+            instance: PartInstance { offset, ..self.instance.clone() },
+            footprint: self.footprint.translated(delta),
+        })
+    }
+}
+
+/// A change to the world's contents, as sent to subscribers. Undo, redo
+/// and rollbacks report the changes they make like any other edit.
+#[derive(Debug, Clone)]
+pub enum WorldEvent {
+    Added { id: PartInstanceId, record: InstanceRecord },
+    Removed { id: PartInstanceId },
+    Moved { id: PartInstanceId, from: WorldCell, to: WorldCell },
+    Recolored { id: PartInstanceId, from: Option<String>, to: Option<String> },
+    Relabeled { id: PartInstanceId, from: Option<String>, to: Option<String> },
+}
+
+impl From<&Command> for WorldEvent {
+    fn from(command: &Command) -> Self {
+        match command {
+            Command::Place { id, record } => WorldEvent::Added { id: *id, record: record.clone() },
+            Command::Remove { id, .. } => WorldEvent::Removed { id: *id },
+            Command::Move { id, from, to } => WorldEvent::Moved { id: *id, from: *from, to: *to },
+            Command::Recolor { id, from, to } => WorldEvent::Recolored {
+                id: *id,
+                from: from.clone(),
+                to: to.clone(),
+            },
+            Command::Relabel { id, from, to } => WorldEvent::Relabeled {
+                id: *id,
+                from: from.clone(),
+                to: to.clone(),
+            },
+        }
+    }
+}
+
+/// The id to hand out after `id`, or an error once ids run out.
+fn next_after(id: u64) -> Result<u64, String> {
+    id.checked_add(1)
+        .ok_or_else(|| format!("instance id {} leaves no room for later ids", id))
+}
+
 #[derive(Debug)]
 pub struct World {
-    occupancy: HashMap<WorldCell, PartInstanceId>,
+    occupancy: ChunkMap<PartInstanceId>,
+    connectors: HashMap<Position, Vec<(PartInstanceId, WorldConnector)>>,
+    protrusions: HashMap<WorldCell, Vec<(PartInstanceId, usize)>>,
+    records: BTreeMap<PartInstanceId, InstanceRecord>,
+    submodels: BTreeMap<String, Submodel>,
+    connections: ConnectionGraph,
+    history: History,
+    check_protrusions: bool,
+    bounds: Option<Bounds>,
+    ground: Option<Ground>,
     next_id: u64,
+    next_submodel_id: u64,
+    subscribers: Vec<Sender<WorldEvent>>,
+    /// Events held back until the outermost transaction settles.
+    pending: Vec<WorldEvent>,
+    transaction_depth: usize,
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl World {
     pub fn new() -> Self {
         Self {
-            occupancy: HashMap::new(),
+            occupancy: ChunkMap::new(),
+            connectors: HashMap::new(),
+            protrusions: HashMap::new(),
+            records: BTreeMap::new(),
+            submodels: BTreeMap::new(),
+            connections: ConnectionGraph::new(),
+            history: History::default(),
+            check_protrusions: true,
+            bounds: None,
+            ground: None,
             next_id: 1,
+            next_submodel_id: 1,
+            subscribers: Vec::new(),
+            pending: Vec::new(),
+            transaction_depth: 0,
         }
     }
 
     pub fn is_occupied(&self, cell: WorldCell) -> bool {
-        self.occupancy.contains_key(&cell)
+        self.occupancy.contains(cell)
+    }
+
+    pub fn instance_at(&self, cell: WorldCell) -> Option<PartInstanceId> {
+        self.occupancy.get(cell)
+    }
+
+    /// Occupied cells inside `region` (inclusive), ordered by `(z, y, x)`.
+    pub fn cells_in(&self, region: &Bounds) -> Vec<(WorldCell, PartInstanceId)> {
+        self.occupancy.region(region)
+    }
+
+    /// Instances with at least one cell inside `region`.
+    pub fn instances_in(&self, region: &Bounds) -> BTreeSet<PartInstanceId> {
+        self.occupancy.region(region).into_iter().map(|(_, id)| id).collect()
+    }
+
+    pub fn instance(&self, id: PartInstanceId) -> Option<&PartInstance> {
+        self.records.get(&id).map(|r| &r.instance)
+    }
+
+    /// All placed instances in id order.
+    pub fn instances(&self) -> impl Iterator<Item = (PartInstanceId, &PartInstance)> + '_ {
+        self.records.iter().map(|(id, r)| (*id, &r.instance))
+    }
+
+    pub fn instance_count(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Cells occupied by one instance.
+    pub fn instance_cells(&self, id: PartInstanceId) -> Option<&[WorldCell]> {
+        self.records.get(&id).map(|r| r.footprint.cells.as_slice())
+    }
+
+    /// Lowest layer an instance occupies.
+    pub fn bottom_layer(&self, id: PartInstanceId) -> Option<i32> {
+        self.instance_cells(id)?.iter().map(|c| c.z).min()
+    }
+
+    pub fn footprint(&self, id: PartInstanceId) -> Option<&Footprint> {
+        self.records.get(&id).map(|r| &r.footprint)
+    }
+
+    /// Recolors an instance. Setting the color it already has is not an
+    /// edit and leaves history alone.
+    pub fn set_color(&mut self, id: PartInstanceId, color: Option<String>) -> Result<(), String> {
+        let instance = self.instance_mut(id)?;
+        if instance.color == color {
+            return Ok(());
+        }
+        let from = std::mem::replace(&mut instance.color, color.clone());
+        self.record(Command::Recolor { id, from, to: color });
+        Ok(())
+    }
+
+    /// Relabels an instance; like [`World::set_color`], an undoable edit.
+    pub fn set_label(&mut self, id: PartInstanceId, label: Option<String>) -> Result<(), String> {
+        let instance = self.instance_mut(id)?;
+        if instance.label == label {
+            return Ok(());
+        }
+        let from = std::mem::replace(&mut instance.label, label.clone());
+        self.record(Command::Relabel { id, from, to: label });
+        Ok(())
+    }
+
+    fn instance_mut(&mut self, id: PartInstanceId) -> Result<&mut PartInstance, String> {
+        self.records
+            .get_mut(&id)
+            .map(|r| &mut r.instance)
+            .ok_or_else(|| format!("unknown instance: {:?}", id))
     }
 
     pub fn occupancy_len(&self) -> usize {
         self.occupancy.len()
     }
 
+    pub fn connections(&self) -> &ConnectionGraph {
+        &self.connections
+    }
+
+    pub fn connector(&self, id: PartInstanceId, index: usize) -> Option<WorldConnector> {
+        self.records
+            .get(&id)
+            .and_then(|r| r.footprint.connectors.get(index))
+            .copied()
+    }
+
+    /// Whether placement treats protruding connectors (studs, pins, ...) as
+    /// soft occupancy. Enabled by default; disable for loose checking.
+    pub fn set_protrusion_checks(&mut self, enabled: bool) {
+        self.check_protrusions = enabled;
+    }
+
+    pub fn protrusion_checks(&self) -> bool {
+        self.check_protrusions
+    }
+
+    /// Restricts placement to an inclusive box of cells. Parts already placed
+    /// are not re-checked.
+    pub fn set_bounds(&mut self, bounds: Option<Bounds>) {
+        self.bounds = bounds;
+    }
+
+    pub fn bounds(&self) -> Option<&Bounds> {
+        self.bounds.as_ref()
+    }
+
+    /// Keeps placement on or above the build surface. Like bounds, parts
+    /// already placed are not re-checked.
+    pub fn set_ground(&mut self, ground: Option<Ground>) {
+        self.ground = ground;
+    }
+
+    pub fn ground(&self) -> Option<&Ground> {
+        self.ground.as_ref()
+    }
+
+    /// Connector pairs `footprint` would mate if it were placed, as
+    /// `(local connector, other instance, other connector)`.
+    pub fn mates_for(
+        &self,
+        footprint: &Footprint,
+    ) -> Vec<(WorldConnector, PartInstanceId, WorldConnector)> {
+        let mut mates = Vec::new();
+        for c in &footprint.connectors {
+            for (other_id, other) in self.connectors.get(&c.pos).into_iter().flatten() {
+                if c.mates(other) {
+                    mates.push((*c, *other_id, *other));
+                }
+            }
+        }
+        mates
+    }
+
+    /// Lists every conflict `footprint` would run into, without stopping at
+    /// the first one.
+    pub fn report_footprint(&self, footprint: &Footprint) -> PlacementReport {
+        let mut report = PlacementReport::default();
+
+        for cell in &footprint.cells {
+            if let Some(other) = self.occupancy.get(*cell) {
+                report.collisions.push((*cell, other));
+            }
+            if let Some(bounds) = &self.bounds {
+                if !bounds.contains(&Cell { x: cell.x, y: cell.y, z: cell.z }) {
+                    report.out_of_bounds.push(*cell);
+                }
+            }
+            if let Some(ground) = &self.ground {
+                if !ground.supports(*cell) {
+                    report.below_ground.push(*cell);
+                }
+            }
+        }
+
+        if !self.check_protrusions {
+            return report;
+        }
+
+        let mates = self.mates_for(footprint);
+
+        for cell in &footprint.cells {
+            for &(other_id, other_index) in self.protrusions.get(cell).into_iter().flatten() {
+                let taken = mates
+                    .iter()
+                    .any(|(_, id, other)| *id == other_id && other.index == other_index);
+                if !taken {
+                    report.unmatched_protrusions.push(ProtrusionConflict::Blocked {
+                        cell: *cell,
+                        instance: other_id,
+                        connector: other_index,
+                    });
+                }
+            }
+        }
+
+        for &(index, cell) in &footprint.protrusions {
+            if let Some(other_id) = self.occupancy.get(cell) {
+                let taken = mates
+                    .iter()
+                    .any(|(c, id, _)| c.index == index && *id == other_id);
+                if !taken {
+                    report.unmatched_protrusions.push(ProtrusionConflict::Intrudes {
+                        cell,
+                        connector: index,
+                        instance: other_id,
+                    });
+                }
+            }
+        }
+
+        report
+    }
+
+    /// Dry run of `place_part_oriented`: reports every conflict the placement
+    /// would hit.
+    pub fn check_placement(
+        &self,
+        part: &Part,
+        offset: WorldCell,
+        orientation: Orientation,
+    ) -> PlacementReport {
+        self.report_footprint(&Footprint::new(part, offset, orientation))
+    }
+
+    /// Checks that `footprint` could be placed: no cell is taken or out of
+    /// bounds, and every protrusion it meets or makes is taken by a mating
+    /// connector.
+    pub fn check_footprint(&self, footprint: &Footprint) -> Result<(), String> {
+        match self.report_footprint(footprint).first_error() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
     pub fn place_part(&mut self, part: &Part, offset: WorldCell) -> Result<PartInstanceId, String> {
+        self.place_part_oriented(part, offset, Orientation::Deg0)
+    }
+
+    pub fn place_part_oriented(
+        &mut self,
+        part: &Part,
+        offset: WorldCell,
+        orientation: Orientation,
+    ) -> Result<PartInstanceId, String> {
         let id = PartInstanceId(self.next_id);
-        self.next_id += 1;
+        self.place_part_with_id(id, part, offset, orientation, None)
+    }
 
-        let cells: Vec<WorldCell> = part
-            .lattice
-            .occupied
-            .iter()
-            .map(|c| WorldCell {
-                x: c.x + offset.x,
-                y: c.y + offset.y,
-                z: c.z + offset.z,
-            })
-            .collect();
+    /// Places every part or none. The batch is checked against the world and
+    /// against itself, and lands as a single undo step.
+    pub fn place_batch(&mut self, batch: &[Placement<'_>]) -> Result<Vec<PartInstanceId>, String> {
+        self.transaction(|world| {
+            batch
+                .iter()
+                .enumerate()
+                .map(|(i, p)| {
+                    world
+                        .place_part_oriented(p.part, p.offset, p.orientation)
+                        .map_err(|e| format!("placement {}: {}", i, e))
+                })
+                .collect()
+        })
+    }
+
+    /// Runs `edits` as one undo step. If they fail, everything they did is
+    /// rolled back, ids, redo history and placement rules included, and the
+    /// error is returned.
+    pub fn transaction<T>(
+        &mut self,
+        edits: impl FnOnce(&mut World) -> Result<T, String>,
+    ) -> Result<T, String> {
+        self.history.begin_group();
+        self.transaction_depth += 1;
+        let checkpoint = self.history.checkpoint();
+        let event_mark = self.pending.len();
+        let next_ids = (self.next_id, self.next_submodel_id);
+        let rules = (self.check_protrusions, self.bounds, self.ground);
 
-        for cell in &cells {
-            if self.is_occupied(*cell) {
-                return Err(format!("cell already occupied: {:?}", cell));
+        let mut result = edits(self);
+
+        if let Err(e) = &result {
+            let mut failed = None;
+            for command in self.history.rewind(checkpoint).iter().rev() {
+                if let Err(undo) = self.apply(&command.inverse()) {
+                    failed.get_or_insert(undo);
+                }
+            }
+            if let Some(undo) = failed {
+                result = Err(format!("{}; rollback failed: {}", e, undo));
             }
+            (self.next_id, self.next_submodel_id) = next_ids;
+            (self.check_protrusions, self.bounds, self.ground) = rules;
+            self.pending.truncate(event_mark);
         }
 
-        for cell in cells {
-            self.occupancy.insert(cell, id);
+        self.history.end_group();
+        self.transaction_depth -= 1;
+        if self.transaction_depth == 0 {
+            for event in std::mem::take(&mut self.pending) {
+                self.broadcast(event);
+            }
         }
+        result
+    }
+
+    /// Places a part under a caller-chosen id, e.g. when loading a saved
+    /// model. Later ids are allocated after it.
+    pub(crate) fn place_part_with_id(
+        &mut self,
+        id: PartInstanceId,
+        part: &Part,
+        offset: WorldCell,
+        orientation: Orientation,
+        submodel: Option<SubmodelRef>,
+    ) -> Result<PartInstanceId, String> {
+        if self.records.contains_key(&id) {
+            return Err(format!("instance id already in use: {:?}", id));
+        }
+
+        let after = next_after(id.0)?;
+        let after_submodel = submodel.as_ref().map(|s| next_after(s.id.0)).transpose()?;
+
+        let footprint = Footprint::new(part, offset, orientation);
+        self.check_footprint(&footprint)?;
+        self.next_id = self.next_id.max(after);
+        if let Some(after) = after_submodel {
+            self.next_submodel_id = self.next_submodel_id.max(after);
+        }
+
+        let instance = PartInstance {
+            part: part.meta.id.clone(),
+            system: part.meta.system.clone(),
+            offset,
+            orientation,
+            color: None,
+            label: None,
+            submodel,
+        };
+        let record = InstanceRecord { instance, footprint };
+        self.attach(id, record.clone());
+        self.record(Command::Place { id, record });
 
         Ok(id)
     }
+
+    /// Adds a named submodel that can then be placed any number of times.
+    pub fn define_submodel(&mut self, submodel: Submodel) -> Result<(), String> {
+        if self.submodels.contains_key(&submodel.name) {
+            return Err(format!("submodel already defined: {}", submodel.name));
+        }
+        self.submodels.insert(submodel.name.clone(), submodel);
+        Ok(())
+    }
+
+    pub fn submodel(&self, name: &str) -> Option<&Submodel> {
+        self.submodels.get(name)
+    }
+
+    /// Defined submodels in name order.
+    pub fn submodels(&self) -> impl Iterator<Item = &Submodel> + '_ {
+        self.submodels.values()
+    }
+
+    /// Places every part of a defined submodel as one unit: all of them or
+    /// none, as a single undo step. Each part stays an ordinary instance
+    /// tagged with the returned id, which is never handed out again.
+    pub fn place_submodel(
+        &mut self,
+        name: &str,
+        catalog: &Catalog,
+        offset: WorldCell,
+        orientation: Orientation,
+    ) -> Result<SubmodelInstanceId, String> {
+        let submodel = self
+            .submodels
+            .get(name)
+            .ok_or_else(|| format!("unknown submodel: {}", name))?
+            .clone();
+        let parts = submodel.resolve(catalog, offset, orientation)?;
+
+        self.transaction(|world| {
+            let sub_id = SubmodelInstanceId(world.next_submodel_id);
+            world.next_submodel_id = next_after(sub_id.0)?;
+            for (i, p) in parts.iter().enumerate() {
+                let id = PartInstanceId(world.next_id);
+                let membership = SubmodelRef { id: sub_id, name: submodel.name.clone() };
+                world
+                    .place_part_with_id(id, p.part, p.offset, p.orientation, Some(membership))
+                    .map_err(|e| format!("submodel {} part {}: {}", name, i, e))?;
+                if let Some(color) = p.color {
+                    world.set_color(id, Some(color.to_string()))?;
+                }
+            }
+            Ok(sub_id)
+        })
+    }
+
+    /// Id the next placed submodel will get.
+    pub(crate) fn next_submodel_id(&self) -> u64 {
+        self.next_submodel_id
+    }
+
+    /// Keeps ids below `next` from being handed out, e.g. ids of copies
+    /// that were removed before a model was saved.
+    pub(crate) fn reserve_submodel_ids(&mut self, next: u64) {
+        self.next_submodel_id = self.next_submodel_id.max(next);
+    }
+
+    /// Placed submodels and the submodel each is a copy of.
+    pub fn submodel_instances(&self) -> BTreeMap<SubmodelInstanceId, &str> {
+        self.records
+            .values()
+            .filter_map(|r| r.instance.submodel.as_ref())
+            .map(|s| (s.id, s.name.as_str()))
+            .collect()
+    }
+
+    /// Part instances making up a placed submodel, in id order.
+    pub fn submodel_parts(&self, id: SubmodelInstanceId) -> Vec<PartInstanceId> {
+        self.records
+            .iter()
+            .filter(|(_, r)| r.instance.submodel.as_ref().map(|s| s.id) == Some(id))
+            .map(|(pid, _)| *pid)
+            .collect()
+    }
+
+    /// Removes every part of a placed submodel as one undo step.
+    pub fn remove_submodel(&mut self, id: SubmodelInstanceId) -> Result<(), String> {
+        let parts = self.submodel_parts(id);
+        if parts.is_empty() {
+            return Err(format!("unknown submodel instance: {:?}", id));
+        }
+        self.remove_parts(&parts)
+    }
+
+    /// Shifts every part of a placed submodel by `by` cells. The parts move
+    /// together, so they only have to clear the rest of the world, not the
+    /// cells the unit itself is leaving. One undo step; on failure nothing
+    /// moves.
+    pub fn move_submodel(&mut self, id: SubmodelInstanceId, by: WorldCell) -> Result<(), String> {
+        let parts = self.submodel_parts(id);
+        if parts.is_empty() {
+            return Err(format!("unknown submodel instance: {:?}", id));
+        }
+
+        self.transaction(|world| {
+            let mut moved = Vec::new();
+            for &part in &parts {
+                let record = world.detach(part).expect("listed above");
+                world.record(Command::Remove { id: part, record: record.clone() });
+                let to = record
+                    .instance
+                    .offset
+                    .checked_offset(by)
+                    .ok_or_else(|| format!("offset of {:?} is out of range", part))?;
+                moved.push((part, record.moved_to(to)?));
+            }
+            for (part, record) in moved {
+                world
+                    .check_footprint(&record.footprint)
+                    .map_err(|e| format!("{:?}: {}", part, e))?;
+                world.attach(part, record.clone());
+                world.record(Command::Place { id: part, record });
+            }
+            Ok(())
+        })
+    }
+
+    /// Removes every listed instance, or none if any is unknown, as a single
+    /// undo step.
+    pub fn remove_parts(&mut self, ids: &[PartInstanceId]) -> Result<(), String> {
+        self.transaction(|world| ids.iter().try_for_each(|id| world.remove_part(*id)))
+    }
+
+    pub fn remove_part(&mut self, id: PartInstanceId) -> Result<(), String> {
+        let record = self
+            .detach(id)
+            .ok_or_else(|| format!("unknown instance: {:?}", id))?;
+        self.record(Command::Remove { id, record });
+        Ok(())
+    }
+
+    /// Moves an instance to `new_offset`, keeping its id and orientation. On
+    /// failure the instance stays where it was.
+    pub fn move_part(&mut self, id: PartInstanceId, new_offset: WorldCell) -> Result<(), String> {
+        let from = self.relocate(id, new_offset)?;
+        self.record(Command::Move { id, from, to: new_offset });
+        Ok(())
+    }
+
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history.set_limit(limit);
+    }
+
+    /// Forgets every undo and redo step. Refused inside an open group or
+    /// transaction, whose edits must stay around to close or roll back.
+    pub fn clear_history(&mut self) -> Result<(), String> {
+        if self.history.in_group() {
+            return Err("cannot clear history inside an open group".to_string());
+        }
+        self.history.clear();
+        Ok(())
+    }
+
+    /// Collects the following edits into a single undo step until the
+    /// matching `end_group`.
+    pub fn begin_group(&mut self) {
+        self.history.begin_group();
+    }
+
+    pub fn end_group(&mut self) {
+        self.history.end_group();
+    }
+
+    /// Reverts the last undo step. Returns `false` when there is none.
+    pub fn undo(&mut self) -> Result<bool, String> {
+        if self.history.in_group() {
+            return Err("cannot undo inside an open group".to_string());
+        }
+        let Some(group) = self.history.take_undo() else {
+            return Ok(false);
+        };
+
+        for command in group.iter().rev() {
+            self.apply(&command.inverse())?;
+        }
+        self.history.push_undone(group);
+        Ok(true)
+    }
+
+    /// Reapplies the last undone step. Returns `false` when there is none.
+    pub fn redo(&mut self) -> Result<bool, String> {
+        if self.history.in_group() {
+            return Err("cannot redo inside an open group".to_string());
+        }
+        let Some(group) = self.history.take_redo() else {
+            return Ok(false);
+        };
+
+        for command in &group {
+            self.apply(command)?;
+        }
+        self.history.push_done(group);
+        Ok(true)
+    }
+
+    /// Applies a recorded command without recording it again. The command
+    /// was valid when it was made and history replays in order, so it is
+    /// not checked against the current ground, bounds or protrusion rules:
+    /// changing those must never make an edit impossible to undo.
+    fn apply(&mut self, command: &Command) -> Result<(), String> {
+        match command {
+            Command::Place { id, record } => {
+                self.attach(*id, record.clone());
+            }
+            Command::Remove { id, .. } => {
+                self.detach(*id)
+                    .ok_or_else(|| format!("unknown instance: {:?}", id))?;
+            }
+            Command::Move { id, to, .. } => {
+                let moved = self
+                    .records
+                    .get(id)
+                    .ok_or_else(|| format!("unknown instance: {:?}", id))?
+                    .moved_to(*to)?;
+                self.detach(*id);
+                self.attach(*id, moved);
+            }
+            Command::Recolor { id, to, .. } => {
+                self.instance_mut(*id)?.color = to.clone();
+            }
+            Command::Relabel { id, to, .. } => {
+                self.instance_mut(*id)?.label = to.clone();
+            }
+        }
+        self.notify(command);
+        Ok(())
+    }
+
+    /// Returns a stream of every later change to the world. Events made
+    /// inside a transaction arrive once it succeeds, and not at all if it
+    /// fails. Dropping the receiver unsubscribes.
+    pub fn subscribe(&mut self) -> Receiver<WorldEvent> {
+        let (tx, rx) = channel();
+        self.subscribers.push(tx);
+        rx
+    }
+
+    /// Records an edit that has already been made and tells subscribers.
+    fn record(&mut self, command: Command) {
+        self.notify(&command);
+        self.history.record(command);
+    }
+
+    fn notify(&mut self, command: &Command) {
+        if self.subscribers.is_empty() {
+            return;
+        }
+        let event = WorldEvent::from(command);
+        if self.transaction_depth > 0 {
+            self.pending.push(event);
+        } else {
+            self.broadcast(event);
+        }
+    }
+
+    fn broadcast(&mut self, event: WorldEvent) {
+        self.subscribers.retain(|tx| tx.send(event.clone()).is_ok());
+    }
+
+    /// Moves an instance without recording it, returning its old offset.
+    fn relocate(&mut self, id: PartInstanceId, new_offset: WorldCell) -> Result<WorldCell, String> {
+        let moved = self
+            .records
+            .get(&id)
+            .ok_or_else(|| format!("unknown instance: {:?}", id))?
+            .moved_to(new_offset)?;
+        let old = self.detach(id).expect("looked up above");
+
+        match self.check_footprint(&moved.footprint) {
+            Ok(()) => {
+                let from = old.instance.offset;
+                self.attach(id, moved);
+                Ok(from)
+            }
+            Err(e) => {
+                self.attach(id, old);
+                Err(e)
+            }
+        }
+    }
+
+    /// Indexes an already validated record under `id`.
+    fn attach(&mut self, id: PartInstanceId, record: InstanceRecord) {
+        let footprint = &record.footprint;
+
+        for cell in &footprint.cells {
+            self.occupancy.insert(*cell, id);
+        }
+
+        self.connections.add_instance(id);
+        for (c, other_id, other) in self.mates_for(footprint) {
+            if let Some(freedom) = c.kind.joint_freedom(other.kind) {
+                self.connections
+                    .connect((id, c.index), (other_id, other.index), freedom);
+            }
+        }
+
+        for c in &footprint.connectors {
+            self.connectors.entry(c.pos).or_default().push((id, *c));
+        }
+
+        for &(index, cell) in &footprint.protrusions {
+            self.protrusions.entry(cell).or_default().push((id, index));
+        }
+
+        self.records.insert(id, record);
+    }
+
+    /// Drops every trace of `id` and hands back its record.
+    fn detach(&mut self, id: PartInstanceId) -> Option<InstanceRecord> {
+        let record = self.records.remove(&id)?;
+        let footprint = &record.footprint;
+
+        for cell in &footprint.cells {
+            self.occupancy.remove(*cell);
+        }
+
+        for c in &footprint.connectors {
+            if let Some(at) = self.connectors.get_mut(&c.pos) {
+                at.retain(|(owner, _)| *owner != id);
+                if at.is_empty() {
+                    self.connectors.remove(&c.pos);
+                }
+            }
+        }
+
+        for (_, cell) in &footprint.protrusions {
+            if let Some(at) = self.protrusions.get_mut(cell) {
+                at.retain(|(owner, _)| *owner != id);
+                if at.is_empty() {
+                    self.protrusions.remove(cell);
+                }
+            }
+        }
+
+        self.connections.remove_instance(id);
+
+        Some(record)
+    }
 }